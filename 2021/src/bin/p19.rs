@@ -1,139 +1,204 @@
 use std::io::{self, BufRead};
-use std::collections::HashSet;
-use anyhow::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use anyhow::{anyhow, Result};
 use regex::Regex;
 use itertools::iproduct;
 
 type Vec3 = [i64; 3];
+type Mat3 = [[i64; 3]; 3];
+
+const IDENTITY: Mat3 = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+
+// 12 beacons in common gives 12 choose 2 shared pairwise distances
+const MIN_MATCHES: usize = 12;
+const MIN_SHARED_DISTANCES: usize = MIN_MATCHES * (MIN_MATCHES - 1) / 2;
 
 #[derive(Debug, Default)]
 struct Scanner {
-    done: bool,
+    id: usize,
     position: Vec3,
+    rotation: Option<Mat3>,
     beacons: Vec<Vec3>,
+    // sorted squared distances between every pair of beacons, these are
+    // independent of orientation so overlapping scanners share many of them
+    fingerprint: Vec<i64>,
 }
 
+impl Scanner {
+    fn calc_fingerprint(&mut self) {
+        self.fingerprint = iproduct!(0..self.beacons.len(), 0..self.beacons.len())
+            .filter(|(i, j)| i < j)
+            .map(|(i, j)| {
+                let d = sub(&self.beacons[i], &self.beacons[j]);
+                d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+            })
+            .collect();
+        self.fingerprint.sort();
+    }
 
-// hack: 48 orderings and axis negations, not 24. Some are invalid. Less efficient, still works. :)
-const ORDER: [Vec3; 6] = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
-const NEG: [Vec3; 8] = [
-    [1, 1, 1],
-    [1, 1, -1],
-    [1, -1, 1],
-    [1, -1, -1],
-    [-1, 1, 1],
-    [-1, 1, -1],
-    [-1, -1, 1],
-    [-1, -1, -1],
-];
+    // size of the multiset intersection of both fingerprints
+    fn shared_distances(&self, other: &Scanner) -> usize {
+        let (a, b) = (&self.fingerprint, &other.fingerprint);
+        let (mut i, mut j, mut shared) = (0, 0, 0);
+
+        while i < a.len() && j < b.len() {
+            match a[i].cmp(&b[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
 
-fn transform(order: Vec3, negate: Vec3, beacons: &Vec<Vec3>) -> Vec<Vec3> {
-    let t = |i, v: Vec3| {negate[i] * v[order[i] as usize]};
+        shared
+    }
+}
 
-    beacons.iter().map(|v| [t(0, *v), t(1, *v), t(2, *v)]).collect()
+fn determinant(m: &Mat3) -> i64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+// the 24 proper rotations are the signed permutation matrices with a
+// determinant of 1, the other 24 with -1 are mirror images
+fn rotations() -> Vec<Mat3> {
+    let orders = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+
+    iproduct!(orders, 0..8)
+        .map(|(order, signs)| {
+            let mut m = [[0; 3]; 3];
+            for row in 0..3 {
+                m[row][order[row]] = if signs & (1 << row) == 0 { 1 } else { -1 };
+            }
+            m
+        })
+        .filter(|m| determinant(m) == 1)
+        .collect()
+}
+
+fn rotate(m: &Mat3, v: &Vec3) -> Vec3 {
+    let r = |row: usize| m[row][0] * v[0] + m[row][1] * v[1] + m[row][2] * v[2];
+
+    [r(0), r(1), r(2)]
 }
 
 fn sub(a: &Vec3, b: &Vec3) -> Vec3 {
     [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
 }
 
+fn add(a: &Vec3, b: &Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
 fn manhatten(a: &Vec3, b: &Vec3) -> i64 {
     (a[0] - b[0]).abs() + (a[1] - b[1]).abs() + (a[2] - b[2]).abs()
 }
 
-fn align(a: &Vec<Vec3>, b: &Vec<Vec3>) -> Option<(Vec<Vec3>, Vec3)> {
-    for order in ORDER {
-        for neg in NEG {
-            let b_r = transform(order, neg, &b);
-            for a_pt in a {
-                for b_pt in &b_r {
-                    let transform_vec = sub(b_pt, a_pt);
-                    let mut matches = 0;
-                    let mut transformed = vec![];
-                    for ob in &b_r {
-                        let reverse_transform = sub(ob, &transform_vec);
-                        if a.contains(&reverse_transform) {
-                            matches += 1;
-                        }
-                        transformed.push(reverse_transform);
-                    }
-                    if matches >= 12 {
-                        return Some((transformed, transform_vec));
-                    }
-                }
+// find the rotation and offset that map b's beacons onto a's. For each
+// rotation every a - b pair votes for an offset, the true offset collects at
+// least MIN_MATCHES votes
+fn align(a: &[Vec3], b: &[Vec3], rotations: &[Mat3]) -> Option<(Mat3, Vec3)> {
+    for r in rotations {
+        let b_r: Vec<Vec3> = b.iter().map(|v| rotate(r, v)).collect();
+
+        let mut votes: HashMap<Vec3, usize> = HashMap::new();
+        for (a_pt, b_pt) in iproduct!(a, &b_r) {
+            let offset = sub(a_pt, b_pt);
+            let count = votes.entry(offset).or_default();
+            *count += 1;
+            if *count >= MIN_MATCHES {
+                return Some((*r, offset));
             }
         }
     }
+
     None
 }
 
+// usage: p19 [--scanners], optionally print each scanner's absolute position
+// and orientation relative to scanner 0
 fn main() -> Result<()> {
-    let mut scanners = parse()?;
+    let verbose = std::env::args().any(|a| a == "--scanners");
 
-    let mut all_beacons: HashSet<Vec3> = HashSet::new();
+    let mut scanners = parse()?;
+    let rotations = rotations();
 
     // scanner 0 is the reference point
-    for b in &scanners[0].beacons {
-        all_beacons.insert(b.clone());
-    }
-    scanners[0].done = true;
+    scanners[0].rotation = Some(IDENTITY);
 
-    while !scanners.iter().all(|x| x.done) {
-        // i should be not done, j should be done
+    let mut queue = VecDeque::from([0]);
+
+    // resolved scanners have their beacons in absolute coordinates so each
+    // newly aligned scanner's rotation and position are absolute too
+    while let Some(j) = queue.pop_front() {
         for i in 0..scanners.len() {
-            if scanners[i].done {
+            if scanners[i].rotation.is_some()
+                || scanners[i].shared_distances(&scanners[j]) < MIN_SHARED_DISTANCES
+            {
                 continue;
             }
-            for j in (0..scanners.len()).filter(|x| scanners[*x].done) {
-                if let Some((transform, loc)) = align(&scanners[j].beacons, &scanners[i].beacons) {
-                    scanners[i].done = true;
-                    scanners[i].beacons = transform.clone();
-                    scanners[i].position = loc;
-                    for pt in &transform {
-                        all_beacons.insert(*pt);
-                    }
-                    break;
-                }
+
+            if let Some((r, offset)) = align(&scanners[j].beacons, &scanners[i].beacons, &rotations) {
+                let s = &mut scanners[i];
+                s.rotation = Some(r);
+                s.position = offset;
+                s.beacons = s.beacons.iter().map(|b| add(&rotate(&r, b), &offset)).collect();
+                queue.push_back(i);
             }
         }
     }
 
+    if let Some(s) = scanners.iter().find(|s| s.rotation.is_none()) {
+        return Err(anyhow!("unable to align scanner {}", s.id));
+    }
+
+    if verbose {
+        for s in &scanners {
+            println!("scanner {:2}: position {:?} rotation {:?}", s.id, s.position, s.rotation.unwrap());
+        }
+    }
+
+    let all_beacons: HashSet<Vec3> = scanners.iter().flat_map(|s| s.beacons.iter().copied()).collect();
+
     println!("Part 1: {}", all_beacons.len());
 
-    let mut distances: Vec<_> = iproduct!(&scanners, &scanners)
+    let max_distance = iproduct!(&scanners, &scanners)
         .map(|(a, b)| manhatten(&a.position, &b.position))
-        .collect();
+        .max()
+        .unwrap_or(0);
 
-    distances.sort_by(|a, b| b.cmp(a)); // reverse
-
-    println!("Part 2: {}", distances[0]);
+    println!("Part 2: {}", max_distance);
 
     Ok(())
 }
 
 fn parse()-> Result<Vec<Scanner>> {
-    let mut scanners = vec![];
+    let mut scanners: Vec<Scanner> = vec![];
 
     let re = Regex::new(r"(-?\d+),(-?\d+),(-?\d+)")?;
 
     for line in io::stdin().lock().lines() {
         let line = line?;
 
-        let cur = scanners.len() - 1;
-
         if line.starts_with("---") {
-            scanners.push(Scanner::default());
-        } else {
-            if let Some(caps) = re.captures(&line) {
-                scanners[cur].beacons.push([
-                    caps[1].parse()?,
-                    caps[2].parse()?,
-                    caps[3].parse()?,
-                ]);
-            }
+            scanners.push(Scanner { id: scanners.len(), ..Default::default() });
+        } else if let Some(caps) = re.captures(&line) {
+            let cur = scanners.last_mut().ok_or(anyhow!("beacon before scanner header"))?;
+            cur.beacons.push([
+                caps[1].parse()?,
+                caps[2].parse()?,
+                caps[3].parse()?,
+            ]);
         }
     }
 
+    for s in scanners.iter_mut() {
+        s.calc_fingerprint();
+    }
+
     Ok(scanners)
 }
-
@@ -1,34 +1,82 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::io::{self, BufRead};
 use regex::Regex;
-use itertools::iproduct;
 
-// * track d100 and pos as 0-9 and 0-99 so we can use % arithmatic
+// * track die and pos as 0 based so we can use % arithmatic
 
-type Cache = HashMap<(u64, u64, u64, u64), (u64, u64)>;
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    board: u64,
+    sides: u64,
+    rolls: u64,
+    target: u64,
+}
+
+const PRACTICE: Rules = Rules { board: 10, sides: 100, rolls: 3, target: 1000 };
+const DIRAC: Rules = Rules { board: 10, sides: 3, rolls: 3, target: 21 };
 
-const WIN: u64 = 21;
+const OVERFLOW: &str = "universe count overflows u128 for these rules";
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct Die {
     count: u64,
     state: u64,
+    sides: u64,
 }
 
 impl Die {
+    fn new(sides: u64) -> Self {
+        Die { count: 0, state: 0, sides }
+    }
+
     fn roll(&mut self) -> u64 {
         self.count += 1;
 
         let r = self.state + 1; // *
 
-        self.state = (self.state + 1) % 100;
+        self.state = (self.state + 1) % self.sides;
 
         r
     }
 }
 
+// universes in which a single player wins on each turn, or is still playing
+// after it. Index 0 is the start before any turns
+#[derive(Debug)]
+struct Outcomes {
+    wins: Vec<u128>,
+    playing: Vec<u128>,
+}
+
+// usage: p21 [--board n] [--sides n] [--rolls n] [--target n] [--distribution]
+// the options change the Dirac dice game, --distribution prints the number of
+// universes won on each turn by each player
 fn main() -> Result<()> {
+    let mut rules = DIRAC;
+    let mut distribution = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || -> Result<u64> {
+            let v = args.next().ok_or(anyhow!("missing value for {}", arg))?;
+            Ok(v.parse()?)
+        };
+
+        match arg.as_str() {
+            "--board" => rules.board = value()?,
+            "--sides" => rules.sides = value()?,
+            "--rolls" => rules.rolls = value()?,
+            "--target" => rules.target = value()?,
+            "--distribution" => distribution = true,
+            _ => return Err(anyhow!("unknown option: {}", arg)),
+        }
+    }
+
+    if rules.board == 0 || rules.sides == 0 || rules.rolls == 0 || rules.target == 0 {
+        return Err(anyhow!("all rules must be non zero: {:?}", rules));
+    }
+
     let re = Regex::new(r"Player (\d) starting position: (\d+)")?;
 
     let mut start: Vec<u64> = vec![];
@@ -39,59 +87,142 @@ fn main() -> Result<()> {
         }
     }
 
-    println!("Part 1: {}", game(0, start[0] - 1, 0, start[1] - 1)); // *
+    if start.len() != 2 {
+        return Err(anyhow!("expected 2 players, found {}", start.len()));
+    }
+
+    // part 1 always plays the practice game, only part 2 uses the options
+    check_start(&start, &PRACTICE)?;
+
+    println!("Part 1: {}", game(start[0] - 1, start[1] - 1, &PRACTICE)); // *
+
+    check_start(&start, &rules)?;
+
+    let p1 = outcomes(start[0] - 1, &rules)?; // *
+    let p2 = outcomes(start[1] - 1, &rules)?;
+
+    // player 1 wins on turn t while player 2 is still playing after t - 1
+    // turns, player 2 wins on turn t while player 1 survived t turns
+    let turns = std::cmp::max(p1.wins.len(), p2.wins.len());
+    let get = |v: &Vec<u128>, t: usize| v.get(t).copied().unwrap_or(0);
 
-    let mut cache: Cache = HashMap::new();
+    let mut totals: (u128, u128) = (0, 0);
 
-    let (a, b) = quantum(&mut cache, 0, start[0] - 1, 0, start[1] - 1); // *
+    for t in 1..turns {
+        let a = get(&p1.wins, t).checked_mul(get(&p2.playing, t - 1));
+        let b = get(&p2.wins, t).checked_mul(get(&p1.playing, t));
+        let (a, b) = a.zip(b).ok_or(anyhow!(OVERFLOW))?;
 
-    println!("Part 2: {}", std::cmp::max(a, b));
+        if distribution && (a > 0 || b > 0) {
+            println!("turn {:2}: player 1 {:20} player 2 {:20}", t, a, b);
+        }
+
+        totals.0 = totals.0.checked_add(a).ok_or(anyhow!(OVERFLOW))?;
+        totals.1 = totals.1.checked_add(b).ok_or(anyhow!(OVERFLOW))?;
+    }
+
+    if distribution {
+        println!("total  : player 1 {:20} player 2 {:20}", totals.0, totals.1);
+    }
+
+    println!("Part 2: {}", std::cmp::max(totals.0, totals.1));
 
     Ok(())
 }
 
-fn game(mut p1_score: u64, mut p1_pos: u64, mut p2_score: u64, mut p2_pos: u64) -> u64 {
-    let mut die = Die::default();
+fn check_start(start: &[u64], rules: &Rules) -> Result<()> {
+    match start.iter().find(|&&s| s == 0 || s > rules.board) {
+        Some(s) => Err(anyhow!("start position {} not on a board of {}", s, rules.board)),
+        None => Ok(()),
+    }
+}
+
+fn game(mut p1_pos: u64, mut p2_pos: u64, rules: &Rules) -> u64 {
+    let mut die = Die::new(rules.sides);
+
+    let mut p1_score = 0;
+    let mut p2_score = 0;
 
     loop {
         // player 1
-        let roll = die.roll() + die.roll() + die.roll();
-        p1_pos = (p1_pos + roll) % 10;
+        let roll: u64 = (0..rules.rolls).map(|_| die.roll()).sum();
+        p1_pos = (p1_pos + roll) % rules.board;
         p1_score += p1_pos + 1; // *
 
-        if p1_score >= 1000 {
+        if p1_score >= rules.target {
             return p2_score * die.count;
         }
 
         // player 2
-        let roll = die.roll() + die.roll() + die.roll();
-        p2_pos = (p2_pos + roll) % 10;
+        let roll: u64 = (0..rules.rolls).map(|_| die.roll()).sum();
+        p2_pos = (p2_pos + roll) % rules.board;
         p2_score += p2_pos + 1; // *
 
-        if p2_score >= 1000 {
+        if p2_score >= rules.target {
             return p1_score * die.count;
         }
     }
 }
 
-fn quantum(cache: &mut Cache, p1_score: u64, p1_pos: u64, p2_score: u64, p2_pos: u64) -> (u64, u64) {
-    if p2_score >= WIN {
-        return (0, 1);
+// number of ways each total can be rolled with all the dice in a turn
+fn roll_distribution(rules: &Rules) -> Result<Vec<(u64, u128)>> {
+    let mut ways: HashMap<u64, u128> = HashMap::from([(0, 1)]);
+
+    for _ in 0..rules.rolls {
+        let mut next = HashMap::new();
+        for (&sum, &n) in &ways {
+            for face in 1..=rules.sides {
+                let e = next.entry(sum + face).or_insert(0u128);
+                *e = e.checked_add(n).ok_or(anyhow!(OVERFLOW))?;
+            }
+        }
+        ways = next;
     }
 
-    if let Some(&s) = cache.get(&(p1_score, p1_pos, p2_score, p2_pos)) {
-        return s;
-    }
+    let mut ways: Vec<_> = ways.into_iter().collect();
+    ways.sort();
 
-    let mut score = (0, 0);
-    for die in iproduct!(1..=3, 1..=3, 1..=3).map(|(d0, d1, d2)| d0 + d1 + d2) {
-        let p1_pos_next = (p1_pos + die) % 10;
-        let (s1, s2) = quantum(cache, p2_score, p2_pos, p1_score + p1_pos_next + 1, p1_pos_next); // *
-        score.0 += s2;
-        score.1 += s1;
-    }
+    Ok(ways)
+}
+
+// players never interact so each one is followed alone, counting universes
+// by (score, pos) turn after turn until every universe has reached the target
+fn outcomes(start: u64, rules: &Rules) -> Result<Outcomes> {
+    let rolls = roll_distribution(rules)?;
+    let (board, target) = (rules.board as usize, rules.target as usize);
+
+    // universes[score][pos] for scores below the target
+    let mut universes = vec![vec![0u128; board]; target];
+    universes[0][start as usize] = 1;
+
+    let mut o = Outcomes { wins: vec![0], playing: vec![1] };
+
+    while o.playing.last() != Some(&0) {
+        let mut next = vec![vec![0u128; board]; target];
+        let mut wins = 0;
+
+        for (score, row) in universes.iter().enumerate() {
+            for (pos, &n) in row.iter().enumerate().filter(|(_, &n)| n > 0) {
+                for &(roll, ways) in &rolls {
+                    let p = (pos + roll as usize) % board;
+                    let s = score + p + 1; // *
+
+                    let u = n.checked_mul(ways).ok_or(anyhow!(OVERFLOW))?;
+                    let total = if s >= target { &mut wins } else { &mut next[s][p] };
+                    *total = total.checked_add(u).ok_or(anyhow!(OVERFLOW))?;
+                }
+            }
+        }
 
-    cache.insert((p1_score, p1_pos, p2_score, p2_pos), score);
+        universes = next;
+        o.wins.push(wins);
+        let playing = universes
+            .iter()
+            .flatten()
+            .try_fold(0u128, |acc, &n| acc.checked_add(n))
+            .ok_or(anyhow!(OVERFLOW))?;
+        o.playing.push(playing);
+    }
 
-    score
+    Ok(o)
 }
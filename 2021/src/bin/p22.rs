@@ -1,17 +1,30 @@
 use std::io::{self, BufRead};
 use std::cmp::{min, max};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use regex::Regex;
 
-#[derive(Debug, Clone, Copy)]
+// axis aligned box, all ranges are inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Cuboid {
-    is_on: bool,
     x: (i64, i64),
     y: (i64, i64),
     z: (i64, i64),
 }
 
-const LIMIT: (i64, i64) = (-50, 50);
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    is_on: bool,
+    cuboid: Cuboid,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Strategy {
+    Signed,
+    Disjoint,
+    Compressed,
+}
+
+const LIMIT: Cuboid = Cuboid { x: (-50, 50), y: (-50, 50), z: (-50, 50) };
 
 fn min_max(a: (i64, i64), b: (i64, i64)) -> (i64, i64) {
     (max(a.0, b.0), min(a.1, b.1))
@@ -22,86 +35,194 @@ impl Cuboid {
         (self.x.1 - self.x.0 + 1) * (self.y.1 - self.y.0 + 1) * (self.z.1 - self.z.0 + 1)
     }
 
-    fn sign(&self) -> i64 {
-        if self.is_on { 1 } else { -1 }
-    }
-
     fn intersection(&self, other: &Cuboid) -> Option<Cuboid> {
-        let (min_x, max_x) = min_max(self.x, other.x);
-        let (min_y, max_y) = min_max(self.y, other.y);
-        let (min_z, max_z) = min_max(self.z, other.z);
+        let x = min_max(self.x, other.x);
+        let y = min_max(self.y, other.y);
+        let z = min_max(self.z, other.z);
 
-        if min_x <= max_x && min_y <= max_y && min_z <= max_z {
-            Some(Cuboid { is_on: !other.is_on, x: (min_x, max_x), y: (min_y, max_y), z: (min_z, max_z) })
+        if x.0 <= x.1 && y.0 <= y.1 && z.0 <= z.1 {
+            Some(Cuboid { x, y, z })
         } else {
             None
         }
     }
 
-    fn trim(&self) -> Option<Cuboid> {
-        // negate is_on as intersection flips it for use by calc
-        let c = Cuboid { is_on: !self.is_on, x: LIMIT, y: LIMIT, z: LIMIT };
+    // split self minus other into at most 6 disjoint boxes. Slabs are cut off
+    // along x first, then y, then z, each cut shrinking the remaining core
+    fn subtract(&self, other: &Cuboid) -> Vec<Cuboid> {
+        let i = match self.intersection(other) {
+            Some(i) => i,
+            None => return vec![*self],
+        };
+
+        let mut pieces = vec![];
+        let mut core = *self;
 
-        self.intersection(&c)
+        if core.x.0 < i.x.0 {
+            pieces.push(Cuboid { x: (core.x.0, i.x.0 - 1), ..core });
+        }
+        if i.x.1 < core.x.1 {
+            pieces.push(Cuboid { x: (i.x.1 + 1, core.x.1), ..core });
+        }
+        core.x = i.x;
+
+        if core.y.0 < i.y.0 {
+            pieces.push(Cuboid { y: (core.y.0, i.y.0 - 1), ..core });
+        }
+        if i.y.1 < core.y.1 {
+            pieces.push(Cuboid { y: (i.y.1 + 1, core.y.1), ..core });
+        }
+        core.y = i.y;
+
+        if core.z.0 < i.z.0 {
+            pieces.push(Cuboid { z: (core.z.0, i.z.0 - 1), ..core });
+        }
+        if i.z.1 < core.z.1 {
+            pieces.push(Cuboid { z: (i.z.1 + 1, core.z.1), ..core });
+        }
+
+        pieces
     }
 }
 
+// usage: p22 [signed|disjoint|compressed], defaults to signed
 fn main() -> Result<()> {
-    let cuboids = parse()?;
+    let strategy = match std::env::args().nth(1).as_deref() {
+        None | Some("signed") => Strategy::Signed,
+        Some("disjoint") => Strategy::Disjoint,
+        Some("compressed") => Strategy::Compressed,
+        Some(s) => return Err(anyhow!("unknown strategy: {}", s)),
+    };
 
-    let trimmed: Vec<Cuboid> = cuboids
+    let steps = parse()?;
+
+    let trimmed: Vec<Step> = steps
         .iter()
-        .filter(|x| x.trim().is_some())
-        .map(|x| *x)
+        .filter_map(|s| s.cuboid.intersection(&LIMIT).map(|c| Step { cuboid: c, ..*s }))
         .collect();
 
-    println!("Part 1: {}", calc(&trimmed));
-    println!("Part 2: {}", calc(&cuboids));
+    println!("Part 1: {}", calc(&trimmed, strategy));
+    println!("Part 2: {}", calc(&steps, strategy));
 
     Ok(())
 }
 
-fn calc(cuboids: &Vec<Cuboid>) -> i64 {
-    let mut intersections: Vec<Cuboid> = vec![];
+fn calc(steps: &[Step], strategy: Strategy) -> i64 {
+    match strategy {
+        Strategy::Signed => signed_overlap(steps),
+        Strategy::Disjoint => disjoint_split(steps),
+        Strategy::Compressed => compressed(steps),
+    }
+}
 
-    for cuboid in cuboids {
+// inclusion-exclusion over signed boxes
+fn signed_overlap(steps: &[Step]) -> i64 {
+    let mut signed: Vec<(i64, Cuboid)> = vec![];
+
+    for step in steps {
         // calc intersection with existing cuboids, for each existing cuboid add
-        // new cuboid with negative 'sign' as existing cuboid, this clears the
-        // volume occupied by the new cuboid
-        for idx in 0..intersections.len() {
-            if let Some(i) = cuboid.intersection(&intersections[idx]) {
-                intersections.push(i.clone());
+        // the intersection with the opposite sign, this clears the volume
+        // occupied by the new cuboid
+        for idx in 0..signed.len() {
+            let (sign, c) = signed[idx];
+            if let Some(i) = step.cuboid.intersection(&c) {
+                signed.push((-sign, i));
             }
         }
 
-        // if this is an 'on' cuboid add it to the list of intersections as is,
-        // for 'off' cuboids we leave the volume cleared
-        if cuboid.is_on {
-            intersections.push(cuboid.clone());
+        // if this is an 'on' cuboid add it to the list as is, for 'off' cuboids
+        // we leave the volume cleared
+        if step.is_on {
+            signed.push((1, step.cuboid));
         }
     }
 
     // final volume is the sum of the signed intersection volumes
-    intersections.iter().map(|c| c.volume() * c.sign()).sum()
+    signed.iter().map(|(sign, c)| c.volume() * sign).sum()
+}
+
+// keep a list of disjoint 'on' boxes, carving each new step out of them
+fn disjoint_split(steps: &[Step]) -> i64 {
+    let mut boxes: Vec<Cuboid> = vec![];
+
+    for step in steps {
+        boxes = boxes.iter().flat_map(|b| b.subtract(&step.cuboid)).collect();
+
+        if step.is_on {
+            boxes.push(step.cuboid);
+        }
+    }
+
+    boxes.iter().map(|c| c.volume()).sum()
+}
+
+// sorted unique cell boundaries along one axis, cell n covers the half open
+// range bounds[n]..bounds[n + 1]
+fn compress(ranges: impl Iterator<Item = (i64, i64)>) -> Vec<i64> {
+    let mut bounds: Vec<i64> = ranges.flat_map(|(a, b)| [a, b + 1]).collect();
+    bounds.sort();
+    bounds.dedup();
+
+    bounds
+}
+
+// last step covering each compressed cell wins, sweep x then y and paint z
+fn compressed(steps: &[Step]) -> i64 {
+    let xs = compress(steps.iter().map(|s| s.cuboid.x));
+    let ys = compress(steps.iter().map(|s| s.cuboid.y));
+    let zs = compress(steps.iter().map(|s| s.cuboid.z));
+
+    let mut total = 0;
+    let mut column = vec![false; zs.len()];
+
+    for x in xs.windows(2) {
+        let in_x: Vec<&Step> = steps
+            .iter()
+            .filter(|s| s.cuboid.x.0 <= x[0] && x[0] <= s.cuboid.x.1)
+            .collect();
+
+        for y in ys.windows(2) {
+            column.iter_mut().for_each(|c| *c = false);
+
+            for s in in_x.iter().filter(|s| s.cuboid.y.0 <= y[0] && y[0] <= s.cuboid.y.1) {
+                let z0 = zs.binary_search(&s.cuboid.z.0).unwrap();
+                let z1 = zs.binary_search(&(s.cuboid.z.1 + 1)).unwrap();
+                column[z0..z1].iter_mut().for_each(|c| *c = s.is_on);
+            }
+
+            let len: i64 = zs
+                .windows(2)
+                .zip(&column)
+                .filter(|(_, &on)| on)
+                .map(|(z, _)| z[1] - z[0])
+                .sum();
+
+            total += len * (x[1] - x[0]) * (y[1] - y[0]);
+        }
+    }
+
+    total
 }
 
-fn parse() -> Result<Vec<Cuboid>> {
-    let mut cuboids = vec![];
+fn parse() -> Result<Vec<Step>> {
+    let mut steps = vec![];
 
     let re = Regex::new(r"(on|off) x=(-?\d+)..(-?\d+),y=(-?\d+)..(-?\d+),z=(-?\d+)..(-?\d+)")?;
 
     for line in io::stdin().lock().lines() {
         let line = line?;
         if let Some(caps) = re.captures(&line) {
-            let cuboid = Cuboid {
+            let step = Step {
                 is_on: &caps[1] == "on",
-                x: (caps[2].parse()?, caps[3].parse()?),
-                y: (caps[4].parse()?, caps[5].parse()?),
-                z: (caps[6].parse()?, caps[7].parse()?),
+                cuboid: Cuboid {
+                    x: (caps[2].parse()?, caps[3].parse()?),
+                    y: (caps[4].parse()?, caps[5].parse()?),
+                    z: (caps[6].parse()?, caps[7].parse()?),
+                },
             };
-            cuboids.push(cuboid);
+            steps.push(step);
         }
     }
 
-    Ok(cuboids)
+    Ok(steps)
 }
@@ -1,7 +1,7 @@
 use std::io::{self, BufRead};
-use std::cmp::{min, max};
-use std::collections::HashMap;
-use anyhow::Result;
+use std::cmp::{min, max, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use anyhow::{anyhow, Result};
 
 //       -- no stop --
 //       |   |   |   |
@@ -18,198 +18,360 @@ const ROOM_HALLWAY: [usize; 4] = [2, 4, 6, 8];
 
 const HALLWAY_STOPS: [usize; 11 - 4] = [0, 1, 3, 5, 7, 9, 10];
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct GameState {
-    hallway: [char; 11],
-    rooms: [Vec<char>; NUMBER_OF_ROOMS],
-    cost: u64,
-    room_size: usize,
+const HALLWAY_LEN: usize = 11;
+
+// 3 bits per cell, 11 hallway cells plus 4 rooms must fit in a u128
+const MAX_DEPTH: usize = (128 / 3 - HALLWAY_LEN) / NUMBER_OF_ROOMS;
+
+const EMPTY: u8 = 0;
+
+// the two rows unfolded from the diagram for part 2, top down
+const UNFOLDED: [[char; 4]; 2] = [['D', 'C', 'B', 'A'], ['D', 'B', 'A', 'C']];
+
+// every cell packed into 3 bits, 0 is empty and 1..=4 are amphipods A..=D.
+// Cells 0..11 are the hallway, followed by each room from the bottom up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct State(u128);
+
+impl State {
+    fn get(&self, cell: usize) -> u8 {
+        ((self.0 >> (3 * cell)) & 7) as u8
+    }
+
+    fn set(&mut self, cell: usize, pod: u8) {
+        self.0 &= !(7 << (3 * cell));
+        self.0 |= (pod as u128) << (3 * cell);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    pod: u8,
+    from: usize,
+    to: usize,
+    energy: u64,
+}
+
+struct Burrow {
+    depth: usize,
 }
 
-impl GameState {
-    fn new(r1: &[char], r2: &[char], r3: &[char], r4: &[char]) -> Self {
-        GameState {
-            hallway: ['.'; 11],
-            rooms: [r1.to_vec(), r2.to_vec(), r3.to_vec(), r4.to_vec()],
-            cost: 0,
-            room_size: r1.len(),
+impl Burrow {
+    fn cell(&self, room: usize, slot: usize) -> usize {
+        HALLWAY_LEN + room * self.depth + slot
+    }
+
+    fn encode(&self, rooms: &[Vec<char>]) -> State {
+        let mut state = State(0);
+        for (r, room) in rooms.iter().enumerate() {
+            for (slot, &c) in room.iter().enumerate() {
+                state.set(self.cell(r, slot), pod(c));
+            }
         }
+
+        state
+    }
+
+    fn goal(&self) -> State {
+        let rooms: Vec<Vec<char>> = TYPE.iter().map(|&c| vec![c; self.depth]).collect();
+
+        self.encode(&rooms)
+    }
+
+    fn occupants(&self, state: &State, room: usize) -> usize {
+        (0..self.depth)
+            .take_while(|&slot| state.get(self.cell(room, slot)) != EMPTY)
+            .count()
+    }
+
+    // room only holds its own type from the bottom up to slot (exclusive)
+    fn settled_below(&self, state: &State, room: usize, slot: usize) -> bool {
+        (0..slot).all(|s| state.get(self.cell(room, s)) == room as u8 + 1)
+    }
+
+    // check hallway passable from start to end inclusive
+    fn hallway_open(&self, state: &State, start: usize, end: usize) -> bool {
+        let (x0, x1) = (min(start, end), max(start, end));
+
+        (x0..=x1).all(|i| state.get(i) == EMPTY)
     }
 
-    fn room_open(&self, r: usize) -> bool {
-        if self.rooms[r].is_empty() {
-            return true;
+    fn moves(&self, state: &State) -> Vec<(State, Move)> {
+        let mut moves = vec![];
+
+        // hallway to destination room, the pod's own square doesn't block it
+        for src in HALLWAY_STOPS {
+            let p = state.get(src);
+            if p == EMPTY {
+                continue;
+            }
+
+            let room = p as usize - 1;
+            let door = ROOM_HALLWAY[room];
+            let slot = self.occupants(state, room);
+            let step = if src < door { src + 1 } else { src - 1 };
+
+            if slot < self.depth && self.settled_below(state, room, slot) && self.hallway_open(state, step, door) {
+                let steps = delta(src, door) + self.depth - slot;
+                moves.push(self.apply(state, src, self.cell(room, slot), steps));
+            }
         }
 
-        if self.rooms[r].iter().all(|&x| x == TYPE[r]) && self.rooms[r].len() < self.room_size {
-            return true;
+        // top of room to a hallway stop, only for rooms holding strangers
+        for (room, &door) in ROOM_HALLWAY.iter().enumerate() {
+            let n = self.occupants(state, room);
+            if n == 0 || self.settled_below(state, room, n) {
+                continue;
+            }
+
+            let src = self.cell(room, n - 1);
+            for dst in HALLWAY_STOPS {
+                if self.hallway_open(state, door, dst) {
+                    let steps = delta(door, dst) + self.depth - (n - 1);
+                    moves.push(self.apply(state, src, dst, steps));
+                }
+            }
         }
 
-        false
+        moves
     }
 
-    // check if hallway passable from start to end inclusive
-    fn hallway_open(&self, mut start: usize, end: usize, include_start: bool) -> bool {
-        if !include_start {
-            if start < end {
-                start += 1;
-            } else {
-                start -= 1;
+    fn apply(&self, state: &State, from: usize, to: usize, steps: usize) -> (State, Move) {
+        let pod = state.get(from);
+
+        let mut next = *state;
+        next.set(from, EMPTY);
+        next.set(to, pod);
+
+        (next, Move { pod, from, to, energy: steps as u64 * energy(pod) })
+    }
+
+    // every pod not yet settled has to walk to its door and step in, the
+    // pods entering a room also fill it 1, 2, .. m slots deep. Ignoring
+    // blocking makes this a lower bound so A* stays optimal
+    fn heuristic(&self, state: &State) -> u64 {
+        let mut total = 0;
+        let mut entering = [0; NUMBER_OF_ROOMS];
+
+        for h in HALLWAY_STOPS {
+            let p = state.get(h);
+            if p != EMPTY {
+                let room = p as usize - 1;
+                total += delta(h, ROOM_HALLWAY[room]) as u64 * energy(p);
+                entering[room] += 1;
             }
         }
-        let (x0, x1) = (min(start, end), max(start, end));
-        assert!(x0 <= x1);
-        for i in x0..=x1 {
-            if self.hallway[i] != '.' {
-                return false;
+
+        for (room, &door) in ROOM_HALLWAY.iter().enumerate() {
+            for slot in 0..self.occupants(state, room) {
+                let p = state.get(self.cell(room, slot));
+                let target = p as usize - 1;
+
+                if target == room && self.settled_below(state, room, slot + 1) {
+                    continue;
+                }
+
+                // out of the room and across to the door, leaving our own
+                // room means at least one step each way
+                let across = if target == room {
+                    2
+                } else {
+                    delta(door, ROOM_HALLWAY[target])
+                };
+                total += (self.depth - slot + across) as u64 * energy(p);
+                entering[target] += 1;
             }
         }
-        true
-    }
 
-    fn room_complete(&self, room: usize) -> bool {
-        self.rooms[room].len() == self.room_size && self.rooms[room].iter().all(|&c| c == TYPE[room])
+        for (room, &m) in entering.iter().enumerate() {
+            total += (m * (m + 1) / 2) as u64 * energy(room as u8 + 1);
+        }
+
+        total
     }
 
-    fn rooms_complete(&self) -> bool {
-        for i in 0..4 {
-            if !self.room_complete(i) {
-                return false;
+    // A* over packed states, returns the optimal energy and the moves taken
+    fn solve(&self, start: State) -> Option<(u64, Vec<Move>)> {
+        let goal = self.goal();
+
+        let mut best: HashMap<State, u64> = HashMap::from([(start, 0)]);
+        let mut prev: HashMap<State, (State, Move)> = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((self.heuristic(&start), 0, start))]);
+
+        while let Some(Reverse((_, g, state))) = queue.pop() {
+            if state == goal {
+                let mut path = vec![];
+                let mut s = state;
+                while let Some(&(p, m)) = prev.get(&s) {
+                    path.push(m);
+                    s = p;
+                }
+                path.reverse();
+
+                return Some((g, path));
+            }
+
+            if g > best[&state] {
+                continue;
+            }
+
+            for (next, m) in self.moves(&state) {
+                let ng = g + m.energy;
+                if best.get(&next).is_none_or(|&b| ng < b) {
+                    best.insert(next, ng);
+                    prev.insert(next, (state, m));
+                    queue.push(Reverse((ng + self.heuristic(&next), ng, next)));
+                }
             }
         }
-        true
+
+        None
     }
-}
 
-fn cost(amphipod: char) -> u64 {
-    match amphipod {
-        'A' => 1,
-        'B' => 10,
-        'C' => 100,
-        'D' => 1000,
-        _ => unreachable!(),
+    // the burrow as drawn in the puzzle, rooms top down
+    fn render(&self, state: &State) -> String {
+        let c = |cell: usize| match state.get(cell) {
+            EMPTY => '.',
+            p => TYPE[p as usize - 1],
+        };
+
+        let mut lines = vec!["#".repeat(HALLWAY_LEN + 2)];
+        lines.push(format!("#{}#", (0..HALLWAY_LEN).map(c).collect::<String>()));
+
+        for slot in (0..self.depth).rev() {
+            let row: Vec<String> = (0..NUMBER_OF_ROOMS)
+                .map(|r| c(self.cell(r, slot)).to_string())
+                .collect();
+            let edge = if slot == self.depth - 1 { "##" } else { "  " };
+            lines.push(format!("{}#{}#{}", edge, row.join("#"), edge.trim()));
+        }
+
+        lines.push(format!("  {}", "#".repeat(HALLWAY_LEN - 2)));
+
+        lines.join("\n")
     }
-}
 
-fn dst(amphipod: char) -> usize {
-    match amphipod {
-        'A' => 0,
-        'B' => 1,
-        'C' => 2,
-        'D' => 3,
-        _ => unreachable!(),
+    fn describe(&self, cell: usize) -> String {
+        if cell < HALLWAY_LEN {
+            format!("hallway {}", cell)
+        } else {
+            let room = (cell - HALLWAY_LEN) / self.depth;
+            let slot = (cell - HALLWAY_LEN) % self.depth;
+            // depth counted from the hallway like the puzzle diagram
+            format!("room {} depth {}", TYPE[room], self.depth - slot)
+        }
     }
 }
 
+fn pod(c: char) -> u8 {
+    TYPE.iter().position(|&t| t == c).map_or(EMPTY, |p| p as u8 + 1)
+}
+
+fn energy(pod: u8) -> u64 {
+    10u64.pow(pod as u32 - 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Total,
+    Moves,
+    Replay,
+}
+
+// usage: p23 [--moves|--replay], --moves lists the optimal moves and
+// --replay draws the burrow after each one
 fn main() -> Result<()> {
+    let output = match std::env::args().nth(1).as_deref() {
+        None => Output::Total,
+        Some("--moves") => Output::Moves,
+        Some("--replay") => Output::Replay,
+        Some(a) => return Err(anyhow!("unknown option: {}", a)),
+    };
+
     const ROOM_COLUMNS: [usize; 4] = [3, 5, 7, 9];
 
+    // rooms are listed bottom up
     let mut rooms = [vec![], vec![], vec![], vec![]];
 
     for line in io::stdin().lock().lines() {
         let cells: Vec<_> = line?.chars().collect();
-        if TYPE.contains(&cells[3]) {
+        if cells.len() > 3 && TYPE.contains(&cells[3]) {
             for i in 0..4 {
                 rooms[i].insert(0, cells[ROOM_COLUMNS[i]]);
             }
         }
     }
 
-    let state = GameState::new(&rooms[0], &rooms[1], &rooms[2], &rooms[3]);
-
-    let mut cache:HashMap<GameState, u64> = HashMap::new();
+    println!("Part 1: {}", run(&rooms, output)?);
 
-    println!("Part 1: {}", solve(&mut cache, &state));
-
-    for (i, cells) in [['D', 'D'], ['B', 'C'], ['A', 'B'], ['C', 'A']].iter().enumerate() {
-        rooms[i].insert(1, cells[1]);
-        rooms[i].insert(1, cells[0]);
+    // each row goes in just below the original top row, pushing the rows
+    // already inserted up
+    let top = rooms[0].len() - 1;
+    for row in UNFOLDED {
+        for i in 0..4 {
+            rooms[i].insert(top, row[i]);
+        }
     }
 
-    let state = GameState::new(&rooms[0], &rooms[1], &rooms[2], &rooms[3]);
-
-    let mut cache:HashMap<GameState, u64> = HashMap::new();
-
-    println!("Part 2: {}", solve(&mut cache, &state));
+    println!("Part 2: {}", run(&rooms, output)?);
 
     Ok(())
 }
 
-fn solve(cache: &mut HashMap<GameState, u64>, state: &GameState) -> u64 {
-    if state.rooms_complete() {
-        return state.cost;
-    }
+fn run(rooms: &[Vec<char>], output: Output) -> Result<u64> {
+    let depth = rooms[0].len();
 
-    if let Some(x) = cache.get(&state) {
-        return *x;
+    if depth == 0 || depth > MAX_DEPTH || rooms.iter().any(|r| r.len() != depth) {
+        return Err(anyhow!("unsupported room depth: {}", depth));
     }
 
-    let mut min_cost = u64::MAX;
+    let burrow = Burrow { depth };
 
-    // check hallway to dest room moves
-    for (src_idx, c) in state.hallway.iter().enumerate().filter(|(_, &c)| c != '.') {
-        let dst_room_idx = dst(*c);
-        let dst_idx = ROOM_HALLWAY[dst(*c)];
+    let start = burrow.encode(rooms);
 
-        if state.hallway_open(src_idx, dst_idx, false) && state.room_open(dst_room_idx) {
-            let mut next_state = state.clone();
-            let mut moves = delta(src_idx, dst_idx) - 1; // don't count start square
+    let (total, path) = burrow.solve(start).ok_or(anyhow!("no solution"))?;
 
-            // moves for room
-            moves += state.room_size - state.rooms[dst_room_idx].len();
-
-            // update cost
-            next_state.cost += moves as u64 * cost(*c);
-
-            // update room
-            next_state.rooms[dst_room_idx].push(*c);
-
-            // clear hallway
-            next_state.hallway[src_idx] = '.';
-
-            // recurse
-            min_cost = min(min_cost, solve(cache, &next_state));
+    match output {
+        Output::Total => {}
+        Output::Moves => {
+            for m in &path {
+                println!(
+                    "{} {} -> {} energy {}",
+                    TYPE[m.pod as usize - 1],
+                    burrow.describe(m.from),
+                    burrow.describe(m.to),
+                    m.energy
+                );
+            }
         }
-    }
-
-    // check room to hallway moves
-    for (src_room_idx, room) in state.rooms.iter().enumerate() {
-        // look at rooms that have non home pods anywhere
-        if room.iter().any(|&x| x != TYPE[src_room_idx] ) {
-            // for each hallway stop
-            for hallway_dst_idx in HALLWAY_STOPS {
-                // not vacant
-                if state.hallway[hallway_dst_idx] != '.' {
-                    continue;
-                }
-                // can we move there?
-                if !state.hallway_open(ROOM_HALLWAY[src_room_idx], hallway_dst_idx, true) {
-                    continue;
-                }
-
-                let mut moves = delta(ROOM_HALLWAY[src_room_idx], hallway_dst_idx);
-                moves += state.room_size - room.len();
-
-                let mut next_state = state.clone();
-
-                // update new state
-                let pod = next_state.rooms[src_room_idx].pop().unwrap();
-                next_state.hallway[hallway_dst_idx] = pod;
-
-                next_state.cost += moves as u64 * cost(pod);
-
-                min_cost = min(min_cost, solve(cache, &next_state));
+        Output::Replay => {
+            let mut state = start;
+            let mut energy = 0;
+
+            println!("Energy: 0\n{}\n", burrow.render(&state));
+
+            for m in &path {
+                state.set(m.from, EMPTY);
+                state.set(m.to, m.pod);
+                energy += m.energy;
+
+                println!(
+                    "{} {} -> {}, energy: {}\n{}\n",
+                    TYPE[m.pod as usize - 1],
+                    burrow.describe(m.from),
+                    burrow.describe(m.to),
+                    energy,
+                    burrow.render(&state)
+                );
             }
         }
     }
 
-    cache.insert(state.clone(), min_cost);
-    min_cost
+    Ok(total)
 }
 
 fn delta(x0: usize, x1: usize) -> usize {
     let (x0, x1) = (min(x0, x1), max(x0, x1));
 
-    x1 - x0 + 1
+    x1 - x0
 }
-
@@ -5,10 +5,19 @@ const DIR_LIMIT: u64 = 100_000;
 const DISK_SIZE: u64 = 70_000_000;
 const MIN_FREE: u64 = 30_000_000;
 
-#[derive(Debug, Default)]
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Dir,
+    File,
+}
+
+#[derive(Debug)]
 struct Node {
     name: String,
-    size: u64, // if size > 0 this is a file, dir otherwise
+    kind: Kind,
+    size: u64, // file size, or cumulative size of everything below a dir
     parent: Option<usize>,
     children: Vec<usize>,
 }
@@ -17,94 +26,201 @@ impl Node {
     fn mk_dir(name: &str, parent: Option<usize>) -> Self {
         Node {
             name: name.to_owned(),
+            kind: Kind::Dir,
+            size: 0,
             parent,
-            ..Default::default()
+            children: vec![],
         }
     }
 
     fn mk_file(name: &str, size: u64, parent: Option<usize>) -> Self {
         Node {
             name: name.to_owned(),
+            kind: Kind::File,
             size,
             parent,
-            ..Default::default()
+            children: vec![],
         }
     }
 }
 
-fn main() -> Result<()> {
-    let nodes = parse()?;
+#[derive(Debug)]
+struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    fn new() -> Self {
+        FileSystem {
+            nodes: vec![Node::mk_dir("/", None)],
+        }
+    }
 
-    let mut dir_sizes: Vec<_> = (0..nodes.len())
-        .filter(|&idx| nodes[idx].size == 0)
-        .map(|idx| size(idx, &nodes))
-        .collect();
+    fn child(&self, dir: usize, name: &str) -> Option<usize> {
+        self.nodes[dir]
+            .children
+            .iter()
+            .copied()
+            .find(|&c| self.nodes[c].name == name)
+    }
 
-    let part1: u64 = dir_sizes.iter().filter(|&&n| n < DIR_LIMIT).sum();
+    // ls output can be repeated so only add entries we haven't seen
+    fn add(&mut self, dir: usize, node: Node) -> Result<()> {
+        if let Some(existing) = self.child(dir, &node.name) {
+            let e = &self.nodes[existing];
+            if e.kind != node.kind || (e.kind == Kind::File && e.size != node.size) {
+                return Err(anyhow!("conflicting entry: {}", self.path(existing)));
+            }
+            return Ok(());
+        }
 
-    println!("Part 1: {}", part1);
+        self.nodes.push(node);
+        let idx = self.nodes.len() - 1;
+        self.nodes[dir].children.push(idx);
 
-    dir_sizes.sort_unstable();
+        Ok(())
+    }
 
-    let unused_space = DISK_SIZE - size(0, &nodes);
-    let min_dir_size = MIN_FREE - unused_space;
+    // fill in the cumulative dir sizes, children always come after their
+    // parent so a reverse pass sees every child before its parent
+    fn calc_sizes(&mut self) {
+        for idx in (1..self.nodes.len()).rev() {
+            if let Some(p) = self.nodes[idx].parent {
+                self.nodes[p].size += self.nodes[idx].size;
+            }
+        }
+    }
 
-    if let Some(dir_size) = dir_sizes.iter().skip_while(|&&n| n < min_dir_size).nth(0) {
-        println!("Part 2: {:?}", dir_size);
+    fn path(&self, mut idx: usize) -> String {
+        let mut parts = vec![];
+
+        while let Some(p) = self.nodes[idx].parent {
+            parts.push(self.nodes[idx].name.as_str());
+            idx = p;
+        }
+        parts.reverse();
+
+        format!("/{}", parts.join("/"))
     }
 
-    Ok(())
+    fn lookup(&self, path: &str) -> Option<usize> {
+        path.split('/')
+            .filter(|s| !s.is_empty())
+            .try_fold(ROOT, |dir, name| self.child(dir, name))
+    }
+
+    fn dirs(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&idx| self.nodes[idx].kind == Kind::Dir)
+    }
+
+    // same layout as the puzzle description
+    fn tree(&self, idx: usize, depth: usize) {
+        let n = &self.nodes[idx];
+        let indent = "  ".repeat(depth);
+
+        match n.kind {
+            Kind::Dir => println!("{}- {} (dir)", indent, n.name),
+            Kind::File => println!("{}- {} (file, size={})", indent, n.name, n.size),
+        }
+
+        let mut children = n.children.clone();
+        children.sort_by(|&a, &b| self.nodes[a].name.cmp(&self.nodes[b].name));
+
+        for c in children {
+            self.tree(c, depth + 1);
+        }
+    }
+
+    // every directory, largest first
+    fn du(&self) {
+        let mut dirs: Vec<_> = self.dirs().collect();
+        dirs.sort_by(|&a, &b| self.nodes[b].size.cmp(&self.nodes[a].size));
+
+        for d in dirs {
+            println!("{:>10} {}", self.nodes[d].size, self.path(d));
+        }
+    }
 }
 
-fn size(idx: usize, nodes: &[Node]) -> u64 {
-    let mut dir_size = 0;
+// usage: p07 [--tree | --du | --size <path>]
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let fs = parse()?;
 
-    for n in &nodes[idx].children {
-        if nodes[*n].size > 0 {
-            dir_size += nodes[*n].size;
-        } else {
-            dir_size += size(*n, nodes);
+    match args.first().map(|s| s.as_str()) {
+        Some("--tree") => {
+            fs.tree(ROOT, 0);
+            return Ok(());
         }
+        Some("--du") => {
+            fs.du();
+            return Ok(());
+        }
+        Some("--size") => {
+            let path = args.get(1).ok_or(anyhow!("missing path"))?;
+            let idx = fs.lookup(path).ok_or(anyhow!("no such path: {}", path))?;
+            println!("{}", fs.nodes[idx].size);
+            return Ok(());
+        }
+        Some(a) => return Err(anyhow!("unknown option: {}", a)),
+        None => {}
+    }
+
+    let mut dir_sizes: Vec<_> = fs.dirs().map(|idx| fs.nodes[idx].size).collect();
+
+    let part1: u64 = dir_sizes.iter().filter(|&&n| n < DIR_LIMIT).sum();
+
+    println!("Part 1: {}", part1);
+
+    dir_sizes.sort_unstable();
+
+    let unused_space = DISK_SIZE - fs.nodes[ROOT].size;
+    let min_dir_size = MIN_FREE.saturating_sub(unused_space);
+
+    if let Some(dir_size) = dir_sizes.iter().find(|&&n| n >= min_dir_size) {
+        println!("Part 2: {:?}", dir_size);
     }
 
-    dir_size
+    Ok(())
 }
 
-fn parse() -> Result<Vec<Node>> {
-    let mut nodes: Vec<Node> = vec![];
+fn parse() -> Result<FileSystem> {
+    let mut fs = FileSystem::new();
 
-    nodes.push(Node::mk_dir("/", None));
+    let mut cur_dir = ROOT;
 
-    let mut cur_dir = 0;
+    for (n, line) in io::stdin().lock().lines().enumerate() {
+        let line = line?;
+        let el: Vec<&str> = line.split(' ').collect();
 
-    for line in io::stdin().lock().lines() {
-        let el: Vec<String> = line?.split(" ").map(|s| s.to_owned()).collect();
+        let err = |msg: &str| anyhow!("line {}: {}: {}", n + 1, msg, line);
 
-        if &el == &["$", "cd", "/"] || &el == &["$", "ls"] {
-            continue;
-        } else if el.len() == 2 {
-            // file or directory
-            if el[0] == "dir" {
-                nodes.push(Node::mk_dir(&el[1], Some(cur_dir)));
-            } else {
-                nodes.push(Node::mk_file(&el[1], el[0].parse()?, Some(cur_dir)));
+        match el[..] {
+            ["$", "ls"] => {}
+            ["$", "cd", "/"] => cur_dir = ROOT,
+            ["$", "cd", ".."] => {
+                cur_dir = fs.nodes[cur_dir]
+                    .parent
+                    .ok_or_else(|| err("cd above root"))?;
             }
-
-            let last_index = nodes.len() - 1;
-            nodes[cur_dir].children.push(last_index);
-        } else if el[1] == "cd" {
-            if el[2] == ".." {
-                cur_dir = nodes[cur_dir].parent.ok_or(anyhow!("underflow"))?;
-            } else {
-                for child in &nodes[cur_dir].children {
-                    if nodes[*child].name == el[2] {
-                        cur_dir = *child;
-                        break;
-                    }
-                }
+            ["$", "cd", name] => {
+                cur_dir = fs
+                    .child(cur_dir, name)
+                    .filter(|&c| fs.nodes[c].kind == Kind::Dir)
+                    .ok_or_else(|| err("cd into unknown directory"))?;
+            }
+            ["$", ..] => return Err(err("unknown command")),
+            ["dir", name] => fs.add(cur_dir, Node::mk_dir(name, Some(cur_dir)))?,
+            [size, name] => {
+                let size = size.parse().map_err(|_| err("invalid file size"))?;
+                fs.add(cur_dir, Node::mk_file(name, size, Some(cur_dir)))?;
             }
+            _ => return Err(err("unrecognised line")),
         }
     }
 
-    Ok(nodes)
+    fs.calc_sizes();
+
+    Ok(fs)
 }
@@ -4,67 +4,161 @@ use std::io::{self, BufRead};
 
 type Pt = (i64, i64);
 
-#[derive(Debug, Default)]
-struct State {
-    map: HashSet<Pt>,
-    head: Pt,
-    tail: Vec<Pt>,
+// knot 0 is the head, the last knot is the tail
+#[derive(Debug, Clone)]
+struct Rope {
+    knots: Vec<Pt>,
 }
 
+impl Rope {
+    fn new(len: usize) -> Self {
+        Rope {
+            knots: vec![(0, 0); len],
+        }
+    }
+
+    fn tail(&self) -> Pt {
+        self.knots[self.knots.len() - 1]
+    }
+
+    fn step(&mut self, d: char) {
+        let head = &mut self.knots[0];
+
+        match d {
+            'L' => head.0 -= 1,
+            'R' => head.0 += 1,
+            'U' => head.1 -= 1,
+            'D' => head.1 += 1,
+            _ => unreachable!(),
+        }
+
+        // update tail
+        for idx in 1..self.knots.len() {
+            let h = self.knots[idx - 1];
+            move_tail(h, &mut self.knots[idx]);
+        }
+    }
+}
+
+// usage: p09 [--frames <knots> <first move> <last move>]
+// --frames prints the rope after every step of the given (1 based, inclusive)
+// range of moves
 fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
     let mut moves = vec![];
 
     for line in io::stdin().lock().lines() {
         let line = line?;
 
-        let tokens: Vec<_> = line.split(" ").collect();
+        let tokens: Vec<_> = line.split(' ').collect();
         let d = tokens[0].chars().next().ok_or(anyhow!("parse error"))?;
-        let n = tokens[1].parse::<i64>()?;
+        if !"LRUD".contains(d) || tokens.len() != 2 {
+            return Err(anyhow!("invalid move: {}", line));
+        }
+        let n = tokens[1].parse::<usize>()?;
         moves.push((d, n));
     }
 
-    println!("Part 1: {}", process(&moves, 1));
+    if let Some("--frames") = args.first().map(|s| s.as_str()) {
+        let params = args[1..]
+            .iter()
+            .map(|a| a.parse())
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        if let [knots, first, last] = params[..] {
+            if knots == 0 || first == 0 || first > last || last > moves.len() {
+                return Err(anyhow!("invalid frame range"));
+            }
+            animate(&moves, knots, first, last);
+            return Ok(());
+        }
+
+        return Err(anyhow!("usage: --frames <knots> <first move> <last move>"));
+    }
+
+    println!("Part 1: {}", process(&moves, 2));
 
-    println!("Part 2: {}", process(&moves, 9));
+    println!("Part 2: {}", process(&moves, 10));
 
     Ok(())
 }
 
-fn process(moves: &[(char, i64)], tail_n: usize) -> usize {
-    let mut state = State::default();
+// knot positions after every step, one entry per step of each move
+fn simulate(moves: &[(char, usize)], knots: usize) -> Vec<Vec<Vec<Pt>>> {
+    let mut rope = Rope::new(knots);
+
+    moves
+        .iter()
+        .map(|&(d, n)| {
+            (0..n)
+                .map(|_| {
+                    rope.step(d);
+                    rope.knots.clone()
+                })
+                .collect()
+        })
+        .collect()
+}
 
-    for _ in 0..tail_n {
-        state.tail.push((0, 0));
-    }
+fn process(moves: &[(char, usize)], knots: usize) -> usize {
+    let mut rope = Rope::new(knots);
+    let mut visited = HashSet::from([rope.tail()]);
 
-    for m in moves {
-        move_head(*m, &mut state);
+    for &(d, n) in moves {
+        for _ in 0..n {
+            rope.step(d);
+            visited.insert(rope.tail());
+        }
     }
 
-    state.map.len()
+    visited.len()
 }
 
-fn move_head(m: (char, i64), state: &mut State) {
-    for _ in 0..m.1 {
-        match m.0 {
-            'L' => state.head.0 -= 1,
-            'R' => state.head.0 += 1,
-            'U' => state.head.1 -= 1,
-            'D' => state.head.1 += 1,
-            _ => unreachable!(),
-        }
+fn animate(moves: &[(char, usize)], knots: usize, first: usize, last: usize) {
+    let steps = simulate(&moves[..last], knots);
 
-        // update tail
-        let mut h = state.head;
+    // fixed bounds so every frame lines up, always include the start
+    let shown = &steps[first - 1..last];
+    let all = shown.iter().flatten().flatten().chain([&(0, 0)]);
+    let (mut min, mut max) = ((0, 0), (0, 0));
+    for p in all {
+        min = (min.0.min(p.0), min.1.min(p.1));
+        max = (max.0.max(p.0), max.1.max(p.1));
+    }
+
+    for (i, frames) in shown.iter().enumerate() {
+        let (d, n) = moves[first - 1 + i];
+        println!("== {} {} ==\n", d, n);
 
-        for idx in 0..state.tail.len() {
-            move_tail(h, &mut state.tail[idx]);
-            h = state.tail[idx];
+        for knots in frames {
+            println!("{}\n", render(knots, min, max));
         }
+    }
+}
 
-        let last = state.tail.len() - 1;
-        state.map.insert(state.tail[last]);
+// like the puzzle examples, H for the head then knot numbers, or T for a
+// two knot rope. Earlier knots cover later ones and s marks the start
+fn render(knots: &[Pt], min: Pt, max: Pt) -> String {
+    let w = (max.0 - min.0 + 1) as usize;
+    let h = (max.1 - min.1 + 1) as usize;
+
+    let mut grid = vec![vec!['.'; w]; h];
+    grid[(-min.1) as usize][(-min.0) as usize] = 's';
+
+    for (i, k) in knots.iter().enumerate().rev() {
+        let c = match i {
+            0 => 'H',
+            1 if knots.len() == 2 => 'T',
+            _ => char::from_digit(i as u32, 36).unwrap_or('#'),
+        };
+        grid[(k.1 - min.1) as usize][(k.0 - min.0) as usize] = c;
     }
+
+    grid.iter()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn move_tail(head: Pt, tail: &mut Pt) {
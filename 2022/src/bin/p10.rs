@@ -1,58 +1,135 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::io::{self, BufRead};
 
 const CRT_WIDTH: usize = 40;
 const CRT_HEIGHT: usize = 6;
 
-#[derive(Debug)]
-enum Instruction {
-    Noop,
-    Addx(i64),
+// signal strength is sampled on the 20th cycle and every 40 after that
+const PROBE_FIRST: usize = 20;
+const PROBE_STEP: usize = 40;
+
+// name, cycles taken, operand count and the effect on X once complete
+struct Spec {
+    name: &'static str,
+    cycles: usize,
+    operands: usize,
+    effect: fn(i64, &[i64]) -> i64,
+}
+
+const INSTRUCTIONS: [Spec; 2] = [
+    Spec {
+        name: "noop",
+        cycles: 1,
+        operands: 0,
+        effect: |x, _| x,
+    },
+    Spec {
+        name: "addx",
+        cycles: 2,
+        operands: 1,
+        effect: |x, args| x + args[0],
+    },
+];
+
+struct Instruction {
+    spec: &'static Spec,
+    args: Vec<i64>,
+}
+
+#[derive(Debug, Clone)]
+enum Probes {
+    Every { first: usize, step: usize },
+    At(Vec<usize>),
+}
+
+impl Probes {
+    fn contains(&self, cycle: usize) -> bool {
+        match self {
+            Probes::Every { first, step } => {
+                cycle >= *first && (cycle - first).is_multiple_of(*step)
+            }
+            Probes::At(cycles) => cycles.contains(&cycle),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    width: usize,
+    height: usize,
+    probes: Probes,
 }
 
-struct CPU<'a> {
+#[derive(Debug, Clone, Copy)]
+struct Trace {
+    cycle: usize,
+    x_before: i64,
+    x_after: i64,
+    // (x, y, lit), cycles past the end of the screen draw nothing
+    pixel: Option<(usize, usize, bool)>,
+}
+
+struct Cpu<'a> {
     reg_x: i64,
     cycle: usize,
-    instructions: &'a [Instruction],
+    config: &'a Config,
     signals: Vec<i64>,
     crt: Vec<Vec<char>>,
+    trace: Vec<Trace>,
 }
 
-impl<'a> CPU<'a> {
-    fn new(instructions: &'a [Instruction]) -> Self {
+impl<'a> Cpu<'a> {
+    fn new(config: &'a Config) -> Self {
         Self {
             reg_x: 1,
             cycle: 0,
-            instructions,
+            config,
             signals: vec![],
-            crt: vec![vec![' '; CRT_WIDTH]; CRT_HEIGHT],
+            crt: vec![vec![' '; config.width]; config.height],
+            trace: vec![],
         }
     }
 
-    fn tick(&mut self) {
+    // X during the cycle is the value before any effect at the end of it
+    fn tick(&mut self) -> Trace {
         self.cycle += 1;
 
-        if self.cycle == 20 || (self.cycle + 20) % 40 == 0 {
+        if self.config.probes.contains(self.cycle) {
             self.signals.push(self.cycle as i64 * self.reg_x);
         }
 
-        let x = (self.cycle - 1) % CRT_WIDTH;
-        let y = (self.cycle - 1) / CRT_WIDTH;
+        let x = (self.cycle - 1) % self.config.width;
+        let y = (self.cycle - 1) / self.config.width;
+
+        let pixel = if y < self.config.height {
+            let lit = x as i64 >= self.reg_x - 1 && x as i64 <= self.reg_x + 1;
+            if lit {
+                self.crt[y][x] = '#';
+            }
+            Some((x, y, lit))
+        } else {
+            None
+        };
 
-        if x as i64 >= self.reg_x - 1 && x as i64 <= self.reg_x + 1 {
-            self.crt[y][x] = '#';
+        Trace {
+            cycle: self.cycle,
+            x_before: self.reg_x,
+            x_after: self.reg_x,
+            pixel,
         }
     }
 
-    fn run(&mut self) {
-        for ins in self.instructions {
-            match ins {
-                Instruction::Noop => self.tick(),
-                Instruction::Addx(n) => {
-                    self.tick();
-                    self.tick();
-                    self.reg_x += n;
+    fn run(&mut self, instructions: &[Instruction]) {
+        for ins in instructions {
+            for c in 1..=ins.spec.cycles {
+                let mut t = self.tick();
+
+                if c == ins.spec.cycles {
+                    self.reg_x = (ins.spec.effect)(self.reg_x, &ins.args);
+                    t.x_after = self.reg_x;
                 }
+
+                self.trace.push(t);
             }
         }
     }
@@ -61,27 +138,81 @@ impl<'a> CPU<'a> {
 fn parse() -> Result<Vec<Instruction>> {
     let mut instructions = vec![];
 
-    for line in io::stdin().lock().lines() {
+    for (n, line) in io::stdin().lock().lines().enumerate() {
         let line = line?;
-        let tokens: Vec<_> = line.split(" ").collect();
+        let tokens: Vec<_> = line.split(' ').collect();
 
-        let ins = match tokens[0] {
-            "noop" => Instruction::Noop,
-            "addx" => Instruction::Addx(tokens[1].parse()?),
-            _ => unreachable!(),
-        };
+        let spec = INSTRUCTIONS
+            .iter()
+            .find(|s| s.name == tokens[0])
+            .ok_or(anyhow!("line {}: unknown instruction: {}", n + 1, line))?;
+
+        if tokens.len() != spec.operands + 1 {
+            return Err(anyhow!("line {}: wrong operand count: {}", n + 1, line));
+        }
+
+        let args = tokens[1..]
+            .iter()
+            .map(|t| t.parse())
+            .collect::<Result<_, _>>()?;
 
-        instructions.push(ins);
+        instructions.push(Instruction { spec, args });
     }
 
     Ok(instructions)
 }
 
+// usage: p10 [--trace] [--screen WxH] [--probes c1,c2,..]
 fn main() -> Result<()> {
+    let mut config = Config {
+        width: CRT_WIDTH,
+        height: CRT_HEIGHT,
+        probes: Probes::Every {
+            first: PROBE_FIRST,
+            step: PROBE_STEP,
+        },
+    };
+    let mut trace = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => trace = true,
+            "--screen" => {
+                let v = args.next().ok_or(anyhow!("missing screen size"))?;
+                let (w, h) = v.split_once('x').ok_or(anyhow!("invalid screen: {}", v))?;
+                config.width = w.parse()?;
+                config.height = h.parse()?;
+                if config.width == 0 {
+                    return Err(anyhow!("screen width must be non zero"));
+                }
+            }
+            "--probes" => {
+                let v = args.next().ok_or(anyhow!("missing probe cycles"))?;
+                let cycles = v.split(',').map(|c| c.parse()).collect::<Result<_, _>>()?;
+                config.probes = Probes::At(cycles);
+            }
+            _ => return Err(anyhow!("unknown option: {}", arg)),
+        }
+    }
+
     let instructions = parse()?;
 
-    let mut cpu = CPU::new(&instructions);
-    cpu.run();
+    let mut cpu = Cpu::new(&config);
+    cpu.run(&instructions);
+
+    if trace {
+        for t in &cpu.trace {
+            let pixel = match t.pixel {
+                Some((x, y, lit)) => format!("({}, {}) {}", x, y, if lit { '#' } else { '.' }),
+                None => "-".to_string(),
+            };
+            println!(
+                "cycle {:4} x {:4} -> {:4} pixel {}",
+                t.cycle, t.x_before, t.x_after, pixel
+            );
+        }
+    }
 
     println!("Part 1: {}", cpu.signals.iter().sum::<i64>());
 
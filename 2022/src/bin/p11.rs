@@ -1,118 +1,327 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::io::{self, Read};
 
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Old,
+    Const(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Add,
+    Mul,
+}
+
+// right hand side of "new = old * 19" etc
+#[derive(Debug, Clone, Copy)]
+struct Expr {
+    lhs: Operand,
+    op: Operator,
+    rhs: Operand,
+}
+
+impl Operand {
+    fn value(&self, old: i64) -> i64 {
+        match self {
+            Operand::Old => old,
+            Operand::Const(n) => *n,
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, old: i64) -> i64 {
+        let (a, b) = (self.lhs.value(old), self.rhs.value(old));
+
+        match self.op {
+            Operator::Add => a + b,
+            Operator::Mul => a * b,
+        }
+    }
+
+    // wording used in the puzzle's example walkthrough
+    fn describe(&self) -> String {
+        let by = match self.rhs {
+            Operand::Old => "itself".to_string(),
+            Operand::Const(n) => n.to_string(),
+        };
+
+        match self.op {
+            Operator::Add => format!("increases by {}", by),
+            Operator::Mul => format!("is multiplied by {}", by),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Old => write!(f, "old"),
+            Operand::Const(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            Operator::Add => '+',
+            Operator::Mul => '*',
+        };
+
+        write!(f, "{} {} {}", self.lhs, op, self.rhs)
+    }
+}
+
+#[derive(Debug, Clone)]
 struct Monkey {
-    items: Vec<i64>,
-    op: Box<dyn Fn(i64) -> i64>,
+    items: VecDeque<i64>,
+    op: Expr,
     test_div: i64,
     test_true: usize,
     test_false: usize,
 }
 
 impl Monkey {
-    fn new() -> Self {
-        Monkey {
-            items: vec![],
-            op: Box::new(|x| x),
-            test_div: 0,
-            test_true: 0,
-            test_false: 0,
+    fn target(&self, wl: i64) -> usize {
+        if wl % self.test_div == 0 {
+            self.test_true
+        } else {
+            self.test_false
         }
     }
 }
 
+// usage: p11 [--verbose], --verbose dumps each part 1 round like the puzzle
+// example
 fn main() -> Result<()> {
+    let verbose = std::env::args().any(|a| a == "--verbose");
+
     let mut buffer = String::new();
     io::stdin().lock().read_to_string(&mut buffer)?;
 
-    let mut monkeys1 = vec![];
-    let mut monkeys2 = vec![];
+    let mut monkeys = vec![];
 
     for monkey in buffer.split("\n\n") {
-        monkeys1.push(parse_monkey(monkey)?);
-        monkeys2.push(parse_monkey(monkey)?);
+        monkeys.push(parse_monkey(monkey)?);
     }
 
-    let part1 = process(&mut monkeys1, false);
+    let part1 = process(&mut monkeys.clone(), 20, verbose);
 
     println!("Part 1: {}", part1);
 
-    let part2 = process(&mut monkeys2, true);
+    let part2 = process_items(&monkeys, 10000);
 
     println!("Part 2: {}", part2);
 
     Ok(())
 }
 
-fn process(monkeys: &mut [Monkey], part2: bool) -> i64 {
-    let worry_mod = monkeys.iter().map(|m| m.test_div).product::<i64>();
+fn monkey_business(mut inspected_counts: Vec<i64>) -> i64 {
+    inspected_counts.sort_unstable_by(|a, b| b.cmp(a));
 
-    let mut inspected_counts = vec![0; monkeys.len()];
+    inspected_counts.iter().take(2).product::<i64>()
+}
 
-    let rounds = if part2 { 10000 } else { 20 };
+// round by round simulation with relief dividing worry by 3
+fn process(monkeys: &mut [Monkey], rounds: usize, verbose: bool) -> i64 {
+    let mut inspected_counts = vec![0; monkeys.len()];
 
-    for _ in 0..rounds {
+    for round in 1..=rounds {
         for idx in 0..monkeys.len() {
+            if verbose {
+                println!("Monkey {}:", idx);
+            }
+
             // inspect each item
-            while !monkeys[idx].items.is_empty() {
+            while let Some(mut wl) = monkeys[idx].items.pop_front() {
                 inspected_counts[idx] += 1;
-                // get item
-                let mut wl = monkeys[idx].items.remove(0);
+
+                let m = &monkeys[idx];
+
+                if verbose {
+                    println!("  Monkey inspects an item with a worry level of {}.", wl);
+                }
 
                 // inspect
-                wl = (monkeys[idx].op)(wl);
+                wl = m.op.eval(wl);
+
+                if verbose {
+                    println!("    Worry level {} to {}.", m.op.describe(), wl);
+                }
 
                 // adjust worry
-                wl = if part2 { wl % worry_mod } else { wl / 3 };
+                wl /= 3;
+
+                if verbose {
+                    println!(
+                        "    Monkey gets bored with item. Worry level is divided by 3 to {}.",
+                        wl
+                    );
+                    let not = if wl % m.test_div == 0 { "" } else { "not " };
+                    println!(
+                        "    Current worry level is {}divisible by {}.",
+                        not, m.test_div
+                    );
+                }
 
                 // throw
-                if wl % monkeys[idx].test_div == 0 {
-                    let i = monkeys[idx].test_true;
-                    monkeys[i].items.push(wl);
-                } else {
-                    let i = monkeys[idx].test_false;
-                    monkeys[i].items.push(wl);
+                let i = m.target(wl);
+
+                if verbose {
+                    println!(
+                        "    Item with worry level {} is thrown to monkey {}.",
+                        wl, i
+                    );
                 }
+
+                monkeys[i].items.push_back(wl);
             }
         }
-    }
 
-    inspected_counts.sort_unstable_by(|a, b| b.cmp(a));
+        if verbose {
+            println!();
+            println!(
+                "After round {}, the monkeys are holding items with these worry levels:",
+                round
+            );
+            for (idx, m) in monkeys.iter().enumerate() {
+                let items: Vec<String> = m.items.iter().map(|i| i.to_string()).collect();
+                println!("Monkey {}: {}", idx, items.join(", "));
+            }
+            println!();
+        }
+    }
 
-    inspected_counts.iter().take(2).product::<i64>()
+    monkey_business(inspected_counts)
 }
 
-fn parse_monkey(monkey: &str) -> Result<Monkey> {
-    let mut m = Monkey::new();
+// Without relief items never interact so each one is followed on its own.
+// Worry is kept modulo the product of the tests, so the state of an item at
+// the start of a round, (monkey, worry), must repeat and the inspections
+// between repeats can be multiplied out.
+fn process_items(monkeys: &[Monkey], rounds: usize) -> i64 {
+    let worry_mod = monkeys.iter().map(|m| m.test_div).product::<i64>();
 
-    let lines: Vec<_> = monkey.lines().collect();
+    let mut inspected_counts = vec![0; monkeys.len()];
+
+    for (start, m) in monkeys.iter().enumerate() {
+        for &item in &m.items {
+            let mut state = (start, item % worry_mod);
+
+            // inspection counts before each round
+            let mut history: Vec<Vec<i64>> = vec![vec![0; monkeys.len()]];
+            let mut seen: HashMap<(usize, i64), usize> = HashMap::from([(state, 0)]);
+
+            let mut round = 0;
+            while round < rounds {
+                let mut counts = history[round].clone();
+                state = item_round(monkeys, state, worry_mod, &mut counts);
+                history.push(counts);
+                round += 1;
+
+                if let Some(&prev) = seen.get(&state) {
+                    // skip over whole cycles then finish off the remainder
+                    // from the recorded history
+                    let period = round - prev;
+                    let cycles = ((rounds - round) / period) as i64;
+                    let rest = (rounds - round) % period;
 
-    if let Some(items) = lines[1].strip_prefix("  Starting items: ") {
-        for n in items.split(", ") {
-            if let Ok(x) = n.parse() {
-                m.items.push(x);
+                    for i in 0..monkeys.len() {
+                        let per_cycle = history[round][i] - history[prev][i];
+                        let tail = history[prev + rest][i] - history[prev][i];
+                        inspected_counts[i] += history[round][i] + per_cycle * cycles + tail;
+                    }
+                    break;
+                }
+                seen.insert(state, round);
+
+                if round == rounds {
+                    for i in 0..monkeys.len() {
+                        inspected_counts[i] += history[round][i];
+                    }
+                }
             }
         }
     }
 
-    if let Some(operation) = lines[2].strip_prefix("  Operation: new = ") {
-        let tokens: Vec<_> = operation.split(" ").collect();
+    monkey_business(inspected_counts)
+}
 
-        m.op = match tokens[1..] {
-            ["*", "old"] => Box::new(|old| old * old),
-            ["+", "old"] => Box::new(|old| old + old),
-            ["*", _] => {
-                let n = tokens[2].parse::<i64>()?;
-                Box::new(move |old| old * n)
-            }
-            ["+", _] => {
-                let n = tokens[2].parse::<i64>()?;
-                Box::new(move |old| old + n)
-            }
-            _ => unreachable!(),
-        };
+// one round for a single item, thrown items are handled again this round if
+// they land on a monkey that hasn't had its turn yet
+fn item_round(
+    monkeys: &[Monkey],
+    (mut idx, mut wl): (usize, i64),
+    worry_mod: i64,
+    counts: &mut [i64],
+) -> (usize, i64) {
+    loop {
+        counts[idx] += 1;
+        wl = monkeys[idx].op.eval(wl) % worry_mod;
+
+        let next = monkeys[idx].target(wl);
+        let done = next <= idx;
+        idx = next;
+
+        if done {
+            return (idx, wl);
+        }
+    }
+}
+
+fn parse_operand(token: &str) -> Result<Operand> {
+    match token {
+        "old" => Ok(Operand::Old),
+        _ => Ok(Operand::Const(token.parse()?)),
+    }
+}
+
+fn parse_monkey(monkey: &str) -> Result<Monkey> {
+    let lines: Vec<_> = monkey.lines().collect();
+
+    if lines.len() < 6 {
+        return Err(anyhow!("incomplete monkey: {}", monkey));
     }
 
+    let mut items = VecDeque::new();
+
+    if let Some(list) = lines[1].strip_prefix("  Starting items: ") {
+        for n in list.split(", ") {
+            items.push_back(n.parse()?);
+        }
+    }
+
+    let operation = lines[2]
+        .strip_prefix("  Operation: new = ")
+        .ok_or(anyhow!("missing operation: {}", lines[2]))?;
+
+    let tokens: Vec<_> = operation.split(' ').collect();
+
+    let op = match tokens[..] {
+        [lhs, op, rhs] => Expr {
+            lhs: parse_operand(lhs)?,
+            op: match op {
+                "+" => Operator::Add,
+                "*" => Operator::Mul,
+                _ => return Err(anyhow!("unknown operator: {}", op)),
+            },
+            rhs: parse_operand(rhs)?,
+        },
+        _ => return Err(anyhow!("invalid operation: {}", operation)),
+    };
+
+    let mut m = Monkey {
+        items,
+        op,
+        test_div: 0,
+        test_true: 0,
+        test_false: 0,
+    };
+
     if let Some(test) = lines[3].strip_prefix("  Test: divisible by ") {
         m.test_div = test.parse()?;
     }
@@ -1,13 +1,65 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Unexpected, Visitor};
+use serde::Serialize;
+use serde_json::error::Category;
 use std::cmp::{min, Ordering};
+use std::fmt;
 use std::io::{self, Read};
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+// packets are valid JSON so serde can read and write them directly
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
 enum Packet {
     Int(u64),
     List(Vec<Packet>),
 }
 
+// Written by hand rather than derived as untagged, which only reports that
+// nothing matched. Rejecting each value as it's read lets serde_json say
+// what was wrong and where.
+impl<'de> Deserialize<'de> for Packet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PacketVisitor)
+    }
+}
+
+struct PacketVisitor;
+
+impl<'de> Visitor<'de> for PacketVisitor {
+    type Value = Packet;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "number or list")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Packet, E> {
+        Ok(Packet::Int(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Packet, E> {
+        u64::try_from(v)
+            .map(Packet::Int)
+            .map_err(|_| E::invalid_value(Unexpected::Signed(v), &"non negative number"))
+    }
+
+    // integers too large for u64 are read as floats
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Packet, E> {
+        Err(E::invalid_value(
+            Unexpected::Float(v),
+            &"number that fits in u64",
+        ))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Packet, A::Error> {
+        let mut list = vec![];
+        while let Some(p) = seq.next_element()? {
+            list.push(p);
+        }
+
+        Ok(Packet::List(list))
+    }
+}
+
 impl PartialOrd for Packet {
     fn partial_cmp(&self, other: &Packet) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -40,77 +92,205 @@ impl Ord for Packet {
     }
 }
 
+// same text format as the puzzle input
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Int(n) => write!(f, "{}", n),
+            Packet::List(list) => {
+                write!(f, "[")?;
+                for (i, p) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", p)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    expected: String,
+    found: Option<String>,
+}
+
+impl ParseError {
+    // serde_json reports positions within the string it was given, which is
+    // a single line here, so move the error to the real input line
+    fn from_json(e: serde_json::Error, text: &str, line: usize) -> Self {
+        // messages read like "expected value at line 1 column 4" or
+        // "invalid value: integer `-2`, expected non negative number at ..."
+        let msg = e.to_string();
+        let msg = msg
+            .rsplit_once(" at line ")
+            .map_or(msg.as_str(), |(m, _)| m);
+
+        let (expected, found) = match e.classify() {
+            Category::Eof => ("rest of packet".to_string(), None),
+            Category::Data => match msg.split_once(", expected ") {
+                Some((found, expected)) => {
+                    let found = found.split_once(": ").map_or(found, |(_, f)| f);
+                    (expected.to_string(), Some(found.to_string()))
+                }
+                None => (msg.to_string(), None),
+            },
+            _ => (
+                msg.strip_prefix("expected ").unwrap_or(msg).to_string(),
+                text.chars()
+                    .nth(e.column().saturating_sub(1))
+                    .map(|c| format!("'{}'", c)),
+            ),
+        };
+
+        ParseError {
+            line,
+            column: e.column(),
+            expected,
+            found,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected {}, found {}",
+            self.line,
+            self.column,
+            self.expected,
+            self.found.as_deref().unwrap_or("end of line")
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 struct Parser {
     chars: Vec<char>,
     cur: usize,
+    line: usize,
 }
 
 impl Parser {
-    fn new(chars: Vec<char>) -> Self {
-        Self { chars, cur: 0 }
+    fn new(chars: Vec<char>, line: usize) -> Self {
+        Self {
+            chars,
+            cur: 0,
+            line,
+        }
     }
 
-    fn next(&mut self) -> char {
-        let c = self.chars[self.cur];
-        self.cur += 1;
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.cur).copied()
+    }
 
-        c
+    fn error(&self, expected: &'static str) -> ParseError {
+        self.error_at(self.cur, expected)
     }
 
-    fn matches(&mut self, p: fn(char) -> bool) -> Option<char> {
-        if p(self.chars[self.cur]) {
-            return Some(self.next());
+    fn error_at(&self, pos: usize, expected: &'static str) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: pos + 1,
+            expected: expected.to_string(),
+            found: self.chars.get(pos).map(|c| format!("'{}'", c)),
         }
+    }
+
+    fn matches(&mut self, p: fn(char) -> bool) -> Option<char> {
+        let c = self.peek().filter(|&c| p(c))?;
+        self.cur += 1;
+
+        Some(c)
+    }
 
-        None
+    // a whole line must be exactly one packet
+    fn parse_line(&mut self) -> Result<Packet, ParseError> {
+        let p = self.parse()?;
+
+        match self.peek() {
+            None => Ok(p),
+            Some(_) => Err(self.error("end of line")),
+        }
     }
 
-    fn parse(&mut self) -> Packet {
+    fn parse(&mut self) -> Result<Packet, ParseError> {
         if self.matches(|c| c == '[').is_some() {
             // parse list
             let mut list = vec![];
             if self.matches(|c| c == ']').is_some() {
-                return Packet::List(list);
+                return Ok(Packet::List(list));
             }
             loop {
                 // parse elements
-                let p = self.parse();
+                let p = self.parse()?;
                 list.push(p);
 
-                if self.next() == ']' {
-                    return Packet::List(list);
+                if self.matches(|c| c == ']').is_some() {
+                    return Ok(Packet::List(list));
+                }
+                if self.matches(|c| c == ',').is_none() {
+                    return Err(self.error("',' or ']'"));
                 }
             }
         } else {
             // parse int
-            let mut num = self.next() as u64 - '0' as u64;
+            let start = self.cur;
+            let d = self
+                .matches(|c| c.is_ascii_digit())
+                .ok_or_else(|| self.error("'[' or digit"))?;
+            let mut num = d as u64 - '0' as u64;
 
-            while let Some(d) = self.matches(|c| c.is_digit(10)) {
-                num = num * 10 + d as u64 - '0' as u64;
+            while let Some(d) = self.matches(|c| c.is_ascii_digit()) {
+                num = num
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add(d as u64 - '0' as u64))
+                    .ok_or_else(|| self.error_at(start, "number that fits in u64"))?;
             }
 
-            return Packet::Int(num);
+            Ok(Packet::Int(num))
         }
     }
 }
 
+// usage: p13 [--json], --json reads packets with serde_json instead of the
+// hand written parser
 fn main() -> Result<()> {
+    let json = std::env::args().any(|a| a == "--json");
+
     let mut buffer = String::new();
     io::stdin().lock().read_to_string(&mut buffer)?;
 
-    // add part 2 packets in wrong order so ignored in part 1
-    buffer += "\n[[6]]\n[[2]]\n";
+    let mut packets: Vec<Packet> = vec![];
+    // input lines of the packets in the current pair
+    let mut pair: Vec<usize> = vec![];
 
-    let mut packets = vec![];
+    for (n, line) in buffer.lines().enumerate() {
+        if line.is_empty() {
+            check_pair(&pair)?;
+            pair.clear();
+            continue;
+        }
+
+        let packet = if json {
+            serde_json::from_str(line).map_err(|e| ParseError::from_json(e, line, n + 1))?
+        } else {
+            Parser::new(line.chars().collect(), n + 1).parse_line()?
+        };
 
-    for line in buffer.lines().filter(|&s| s != "") {
-        let mut parser = Parser::new(line.chars().collect());
-        let r = parser.parse();
-        packets.push(r);
+        packets.push(packet);
+        pair.push(n + 1);
     }
+    check_pair(&pair)?;
 
     let part1: usize = packets
-        .chunks(2)
+        .chunks_exact(2)
         .enumerate()
         .filter(|(_, p)| p[0] < p[1])
         .map(|(i, _)| i + 1)
@@ -118,10 +298,11 @@ fn main() -> Result<()> {
 
     println!("Part 1: {}", part1);
 
-    // last two are dividers
-    let n = packets.len();
-    let dividers: Vec<_> = packets.iter().skip(n - 2).cloned().collect();
+    let dividers: Vec<_> = [2, 6]
+        .map(|n| Packet::List(vec![Packet::List(vec![Packet::Int(n)])]))
+        .to_vec();
 
+    packets.extend(dividers.iter().cloned());
     packets.sort_unstable();
 
     let part2: usize = packets
@@ -135,3 +316,95 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+// pairs are separated by blank lines
+fn check_pair(lines: &[usize]) -> Result<()> {
+    match lines {
+        [] | [_, _] => Ok(()),
+        [line] => Err(anyhow!("line {}: packet has no pair", line)),
+        [_, _, line, ..] => Err(anyhow!("line {}: expected blank line after pair", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Packet, ParseError> {
+        Parser::new(line.chars().collect(), 1).parse_line()
+    }
+
+    #[test]
+    fn round_trip() {
+        for line in [
+            "[]",
+            "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+            "[[[]],10]",
+            "[[4,4],4,4,4]",
+        ] {
+            let packet = parse(line).unwrap();
+            let json: Packet = serde_json::from_str(line).unwrap();
+            assert_eq!(packet, json);
+
+            // both writers give the puzzle format back and read back the same
+            assert_eq!(packet.to_string(), line);
+            assert_eq!(serde_json::to_string(&packet).unwrap(), line);
+            assert_eq!(parse(&packet.to_string()).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    fn round_trip_ignores_json_whitespace() {
+        let packet: Packet = serde_json::from_str("[1, [2 ,3], [ ]]").unwrap();
+
+        assert_eq!(packet.to_string(), "[1,[2,3],[]]");
+        assert_eq!(parse(&packet.to_string()).unwrap(), packet);
+        assert_eq!(
+            serde_json::from_str::<Packet>(&serde_json::to_string(&packet).unwrap()).unwrap(),
+            packet
+        );
+    }
+
+    #[test]
+    fn errors_are_positioned() {
+        let e = Parser::new("[1,x]".chars().collect(), 7)
+            .parse_line()
+            .unwrap_err();
+        assert_eq!((e.line, e.column), (7, 4));
+        assert_eq!(e.found.as_deref(), Some("'x'"));
+
+        let e = serde_json::from_str::<Packet>("[1,x]").unwrap_err();
+        let e = ParseError::from_json(e, "[1,x]", 7);
+        assert_eq!((e.line, e.column), (7, 4));
+        assert_eq!(e.found.as_deref(), Some("'x'"));
+        assert_eq!(e.expected, "value");
+    }
+
+    #[test]
+    fn json_type_errors_are_positioned() {
+        let e = serde_json::from_str::<Packet>("[1,-2]").unwrap_err();
+        let e = ParseError::from_json(e, "[1,-2]", 3);
+        assert_eq!((e.line, e.column), (3, 5));
+        assert_eq!(e.found.as_deref(), Some("integer `-2`"));
+        assert_eq!(e.expected, "non negative number");
+
+        let e = serde_json::from_str::<Packet>("[[true]]").unwrap_err();
+        let e = ParseError::from_json(e, "[[true]]", 1);
+        assert_eq!(e.column, 6);
+        assert_eq!(e.found.as_deref(), Some("boolean `true`"));
+        assert_eq!(e.expected, "number or list");
+    }
+
+    #[test]
+    fn large_numbers_rejected() {
+        let line = "[1,99999999999999999999999]";
+
+        let e = parse(line).unwrap_err();
+        assert_eq!(e.column, 4);
+        assert_eq!(e.expected, "number that fits in u64");
+
+        let e = serde_json::from_str::<Packet>(line).unwrap_err();
+        let e = ParseError::from_json(e, line, 1);
+        assert_eq!(e.expected, "number that fits in u64");
+    }
+}
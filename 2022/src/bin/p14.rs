@@ -1,104 +1,195 @@
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
 use std::io::{self, BufRead};
 
 type Pt = (i64, i64);
-type Grid = HashMap<(i64, i64), char>;
 
 const START: Pt = (500, 0);
 
-fn main() -> Result<()> {
-    let mut grid: HashMap<Pt, char> = HashMap::new();
-
-    for line in io::stdin().lock().lines() {
-        let line = line?;
-
-        let points: Vec<_> = line
-            .split(" -> ")
-            .map(|s| {
-                s.split(",")
-                    .filter_map(|s| s.parse::<i64>().ok())
-                    .collect::<Vec<_>>()
-            })
-            .collect();
+const AIR: char = '.';
+const ROCK: char = '#';
+const SAND: char = 'o';
+
+// dense grid covering the rocks and the pile of sand on the part 2 floor
+#[derive(Debug, Clone)]
+struct Cave {
+    cells: Vec<char>,
+    x0: i64,
+    w: i64,
+    floor: i64,
+}
 
-        for w in points.windows(2) {
-            let pt0 = (w[0][0], w[0][1]);
-            let pt1 = (w[1][0], w[1][1]);
-            plot([pt0, pt1], &mut grid);
+impl Cave {
+    fn new(walls: &[Vec<Pt>]) -> Result<Self> {
+        let floor = walls
+            .iter()
+            .flatten()
+            .map(|(_, y)| *y)
+            .max()
+            .ok_or(anyhow!("empty grid"))?
+            + 2;
+
+        // wide enough for every rock and the part 2 pile, which spreads at
+        // most floor cells either side of the start
+        let xs = walls.iter().flatten().map(|(x, _)| *x);
+        let x0 = xs.clone().min().unwrap_or(START.0).min(START.0 - floor - 1);
+        let x1 = xs.max().unwrap_or(START.0).max(START.0 + floor + 1);
+        let w = x1 - x0 + 1;
+
+        let mut cave = Cave {
+            cells: vec![AIR; (w * (floor + 1)) as usize],
+            x0,
+            w,
+            floor,
+        };
+
+        for wall in walls {
+            for pts in wall.windows(2) {
+                cave.plot([pts[0], pts[1]]);
+            }
         }
+
+        Ok(cave)
     }
 
-    let floor = *grid
-        .keys()
-        .map(|(_, y)| y)
-        .max()
-        .ok_or(anyhow!("empty grid"))?
-        + 2;
+    fn idx(&self, p: Pt) -> usize {
+        (p.1 * self.w + p.0 - self.x0) as usize
+    }
 
-    // part 1
-    let mut part1 = 0;
+    fn get(&self, p: Pt) -> char {
+        self.cells[self.idx(p)]
+    }
 
-    while drop_sand(&mut grid, floor, false) {
-        part1 += 1;
+    fn set(&mut self, p: Pt, c: char) {
+        let i = self.idx(p);
+        self.cells[i] = c;
     }
 
-    println!("Part 1: {}", part1);
+    fn plot(&mut self, mut points: [Pt; 2]) {
+        points.sort();
+
+        let (a, b) = (points[0], points[1]);
+
+        if a.0 == b.0 {
+            // vertical
+            for y in a.1..=b.1 {
+                self.set((a.0, y), ROCK);
+            }
+        } else if a.1 == b.1 {
+            // horizontal
+            for x in a.0..=b.0 {
+                self.set((x, a.1), ROCK);
+            }
+        }
+    }
 
-    // clear sand
-    grid.retain(|_, c| *c != 'o');
+    // The path holds the route the previous grain fell along. Everything on
+    // it except the resting spot is still air, so the next grain follows
+    // exactly the same route and can start from the last open position.
+    fn drop_sand(&mut self, path: &mut Vec<Pt>, floor_active: bool) -> bool {
+        let mut s = match path.last() {
+            Some(&s) => s,
+            None => return false, // start is blocked
+        };
+
+        loop {
+            if s.1 > self.floor - 2 && !floor_active {
+                return false;
+            }
+
+            // straight, left then right drop
+            let next = [(s.0, s.1 + 1), (s.0 - 1, s.1 + 1), (s.0 + 1, s.1 + 1)]
+                .into_iter()
+                .find(|&n| n.1 < self.floor && self.get(n) == AIR);
+
+            match next {
+                Some(n) => {
+                    path.push(n);
+                    s = n;
+                }
+                None => {
+                    // rest
+                    self.set(s, SAND);
+                    path.pop();
+                    return true;
+                }
+            }
+        }
+    }
 
-    let mut part2 = 0;
-    while !grid.contains_key(&START) && drop_sand(&mut grid, floor, true) {
-        part2 += 1;
+    // crop to the columns holding rock or sand
+    fn render(&self) -> String {
+        let used = |x: i64| (0..self.floor).any(|y| self.get((x, y)) != AIR);
+        let xs: Vec<i64> = (self.x0..self.x0 + self.w).filter(|&x| used(x)).collect();
+        let (min_x, max_x) = (xs[0].min(START.0), xs[xs.len() - 1].max(START.0));
+
+        (0..self.floor)
+            .map(|y| {
+                (min_x..=max_x)
+                    .map(|x| match self.get((x, y)) {
+                        AIR if (x, y) == START => '+',
+                        c => c,
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
+}
 
-    println!("Part 2: {}", part2);
+// usage: p14 [--frames n], --frames draws the cave after every n grains
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let frames = match args.first().map(|s| s.as_str()) {
+        Some("--frames") => {
+            let n: usize = args
+                .get(1)
+                .ok_or(anyhow!("missing frame interval"))?
+                .parse()?;
+            Some(n.max(1))
+        }
+        Some(a) => return Err(anyhow!("unknown option: {}", a)),
+        None => None,
+    };
 
-    Ok(())
-}
+    let mut walls = vec![];
 
-fn drop_sand(grid: &mut Grid, floor: i64, floor_active: bool) -> bool {
-    let mut s = START;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
 
-    loop {
-        if s.1 > floor - 2 && !floor_active {
-            return false;
-        }
+        let points = line
+            .split(" -> ")
+            .map(|s| {
+                let (x, y) = s.split_once(',').ok_or(anyhow!("invalid point: {}", s))?;
+                Ok((x.parse::<i64>()?, y.parse::<i64>()?))
+            })
+            .collect::<Result<Vec<Pt>>>()?;
 
-        if !grid.contains_key(&(s.0, s.1 + 1)) && s.1 + 1 < floor {
-            // straight drop
-            s.1 += 1;
-        } else if !grid.contains_key(&(s.0 - 1, s.1 + 1)) && s.1 + 1 < floor {
-            // left drop
-            s.0 -= 1;
-            s.1 += 1;
-        } else if !grid.contains_key(&(s.0 + 1, s.1 + 1)) && s.1 + 1 < floor {
-            // right drop
-            s.0 += 1;
-            s.1 += 1;
-        } else {
-            // rest
-            grid.insert(s, 'o');
-            return true;
-        }
+        walls.push(points);
     }
+
+    let cave = Cave::new(&walls)?;
+
+    println!("Part 1: {}", fill(&mut cave.clone(), false, frames));
+
+    println!("Part 2: {}", fill(&mut cave.clone(), true, frames));
+
+    Ok(())
 }
 
-fn plot(mut points: [Pt; 2], grid: &mut Grid) {
-    points.sort();
+fn fill(cave: &mut Cave, floor_active: bool, frames: Option<usize>) -> usize {
+    let mut path = vec![START];
+    let mut grains = 0;
 
-    let (a, b) = (points[0], points[1]);
+    while cave.drop_sand(&mut path, floor_active) {
+        grains += 1;
 
-    if a.0 == b.0 {
-        // vertical
-        for y in a.1..=b.1 {
-            grid.insert((a.0, y), '#');
-        }
-    } else if a.1 == b.1 {
-        // horizontal
-        for x in a.0..=b.0 {
-            grid.insert((x, a.1), '#');
+        if let Some(n) = frames {
+            if grains % n == 0 {
+                println!("== {} grains ==\n{}\n", grains, cave.render());
+            }
         }
     }
+
+    grains
 }
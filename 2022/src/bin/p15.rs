@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use regex::Regex;
 use std::collections::HashSet;
 use std::io::{self, BufRead};
@@ -22,36 +22,26 @@ impl Sensor {
         d <= self.dist()
     }
 
-    fn border_calc(&self, hs: &mut HashSet<Pt>) -> bool {
-        let d = self.dist() + 1; // border
-
-        let mut x0 = self.location.0;
-        let mut x1 = self.location.0;
-
-        for y in (0..=d).rev() {
-            let bc = vec![
-                (x0, self.location.1 - y),
-                (x1, self.location.1 - y),
-                (x0, self.location.1 + y),
-                (x1, self.location.1 + y),
-            ];
-
-            for b in bc {
-                if b.0 < 0 || b.0 > 4000000 || b.1 < 0 || b.1 > 4000000 {
-                    continue;
-                }
-                hs.insert((b.0, b.1));
-            }
+    // inclusive x interval covered on the given row
+    fn row_interval(&self, row: i64) -> Option<(i64, i64)> {
+        let r = self.dist() - (self.location.1 - row).abs();
 
-            x0 -= 1;
-            x1 += 1;
+        if r < 0 {
+            return None;
         }
 
-        false
+        Some((self.location.0 - r, self.location.0 + r))
     }
 }
 
+// usage: p15 [row] [limit], defaults are for the real input. The example
+// input uses: p15 10 20
 fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let row: i64 = args.get(1).map_or(Ok(2000000), |a| a.parse())?;
+    let limit: i64 = args.get(2).map_or(Ok(4000000), |a| a.parse())?;
+
     let re =
         Regex::new(r"Sensor at x=(-?\d+), y=(-?\d+): closest beacon is at x=(-?\d+), y=(-?\d+)")?;
 
@@ -68,50 +58,71 @@ fn main() -> Result<()> {
         }
     }
 
-    const ROW: i64 = 2000000;
+    println!("Part 1: {}", covered(&sensors, row));
 
-    let mut sb = HashSet::new();
-    for s in &sensors {
-        sb.insert(s.location);
-        sb.insert(s.beacon);
-    }
-
-    let mut min_x = *sb.iter().map(|(x, _)| x).min().unwrap();
-    let mut max_x = *sb.iter().map(|(x, _)| x).max().unwrap();
+    let p = uncovered(&sensors, limit).ok_or(anyhow!("no uncovered point found"))?;
 
-    min_x = min_x - 1_000_000;
-    max_x = max_x + 1_000_000;
+    println!("Part 2: {}", p.0 * 4000000 + p.1);
 
-    let mut counter = 0;
+    Ok(())
+}
 
-    'outer: for x in min_x..=max_x {
-        for s in &sensors {
-            if s.in_range((x, ROW)) {
-                if !sb.contains(&(x, ROW)) {
-                    counter += 1;
-                    continue 'outer;
-                }
-            }
+// merge the covered intervals on the row and count their length, minus any
+// beacons sitting on the row as those positions can contain a beacon
+fn covered(sensors: &[Sensor], row: i64) -> i64 {
+    let mut intervals: Vec<_> = sensors.iter().filter_map(|s| s.row_interval(row)).collect();
+    intervals.sort();
+
+    let mut merged: Vec<(i64, i64)> = vec![];
+    for (a, b) in intervals {
+        match merged.last_mut() {
+            Some(last) if a <= last.1 + 1 => last.1 = last.1.max(b),
+            _ => merged.push((a, b)),
         }
     }
 
-    println!("Part 1: {}", counter);
+    let total: i64 = merged.iter().map(|(a, b)| b - a + 1).sum();
+
+    let beacons = sensors
+        .iter()
+        .map(|s| s.beacon)
+        .filter(|b| b.1 == row)
+        .collect::<HashSet<_>>()
+        .len() as i64;
 
-    let mut hs = HashSet::new();
+    total - beacons
+}
 
-    for s in &sensors {
-        s.border_calc(&mut hs);
+// A single uncovered point must lie just outside at least two sensor ranges
+// so it sits on the intersection of their boundary lines. In rotated
+// coordinates (u = x + y, v = x - y) the diamond edges are the lines u = c
+// and v = c so every candidate is an intersection of a u line and a v line.
+fn uncovered(sensors: &[Sensor], limit: i64) -> Option<Pt> {
+    let mut us = HashSet::new();
+    let mut vs = HashSet::new();
+
+    for s in sensors {
+        let d = s.dist() + 1;
+        let (x, y) = s.location;
+
+        us.insert(x + y - d);
+        us.insert(x + y + d);
+        vs.insert(x - y - d);
+        vs.insert(x - y + d);
     }
 
-    'outer: for p in &hs {
-        for s in &sensors {
-            if s.in_range(*p) {
-                continue 'outer;
+    let mut candidates = vec![(0, 0), (0, limit), (limit, 0), (limit, limit)];
+
+    for u in &us {
+        for v in &vs {
+            if (u - v) % 2 == 0 {
+                candidates.push(((u + v) / 2, (u - v) / 2));
             }
         }
-        println!("Part 2. {}", p.0 * 4000000 + p.1);
-        break;
     }
 
-    Ok(())
+    candidates
+        .into_iter()
+        .filter(|p| p.0 >= 0 && p.0 <= limit && p.1 >= 0 && p.1 <= limit)
+        .find(|&p| sensors.iter().all(|s| !s.in_range(p)))
 }
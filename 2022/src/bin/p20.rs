@@ -3,74 +3,302 @@ use std::io::{self, BufRead};
 
 const ENCRYPTION_KEY: i64 = 811589153;
 
-#[derive(Debug, Clone, Copy)]
-struct Node {
-    num: i64,
-    idx: usize,
+const NIL: usize = usize::MAX;
+
+// implicit treap, a node's position is the number of nodes before it in an
+// in-order walk so remove-at and insert-at are O(log n). Nodes are the
+// original indices of the numbers and parent links let us find the current
+// position of any number by walking up to the root.
+struct Treap {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    parent: Vec<usize>,
+    priority: Vec<u64>,
+    size: Vec<usize>,
+    root: usize,
 }
 
-fn main() -> Result<()> {
-    let mut numbers: Vec<Node> = vec![];
+impl Treap {
+    fn new(n: usize) -> Self {
+        let mut rng = XorShift(0x2545f4914f6cdd1d);
+
+        let mut t = Treap {
+            left: vec![NIL; n],
+            right: vec![NIL; n],
+            parent: vec![NIL; n],
+            priority: (0..n).map(|_| rng.next()).collect(),
+            size: vec![1; n],
+            root: NIL,
+        };
 
-    for (i, line) in io::stdin().lock().lines().enumerate() {
-        let line = line?;
-        numbers.push(Node {
-            num: line.parse()?,
-            idx: i,
-        });
+        for i in 0..n {
+            t.root = t.merge(t.root, i);
+        }
+
+        t
     }
 
-    println!("Part 1: {}", mix(&numbers, 1, 1)?);
+    fn size(&self, t: usize) -> usize {
+        if t == NIL {
+            0
+        } else {
+            self.size[t]
+        }
+    }
 
-    println!("Part 2: {}", mix(&numbers, ENCRYPTION_KEY, 10)?);
+    fn update(&mut self, t: usize) {
+        self.size[t] = 1 + self.size(self.left[t]) + self.size(self.right[t]);
 
-    Ok(())
-}
+        for c in [self.left[t], self.right[t]] {
+            if c != NIL {
+                self.parent[c] = t;
+            }
+        }
+    }
 
-fn mix(numbers: &Vec<Node>, key: i64, n: usize) -> Result<i64> {
-    let mut numbers: Vec<_> = numbers
-        .iter()
-        .map(|n| Node {
-            num: n.num * key,
-            idx: n.idx,
-        })
-        .collect();
+    fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL || b == NIL {
+            let t = if a == NIL { b } else { a };
+            if t != NIL {
+                self.parent[t] = NIL;
+            }
+            return t;
+        }
 
-    let l = numbers.len();
+        if self.priority[a] > self.priority[b] {
+            self.right[a] = self.merge(self.right[a], b);
+            self.update(a);
+            self.parent[a] = NIL;
+            a
+        } else {
+            self.left[b] = self.merge(a, self.left[b]);
+            self.update(b);
+            self.parent[b] = NIL;
+            b
+        }
+    }
 
-    for _ in 0..n {
-        for idx in 0..l {
-            // find pos
-            let pos = numbers
-                .iter()
-                .position(|n| n.idx == idx)
-                .ok_or(anyhow!("missing index"))?;
-
-            // rotate to end
-            numbers.rotate_right(l - pos - 1);
-
-            // pop number
-            let n = numbers.pop().ok_or(anyhow!("no numbers"))?;
-
-            // rotate idx to last
-            if n.num < 0 {
-                numbers.rotate_right(n.num.abs() as usize % (l - 1));
-            } else if n.num > 0 {
-                numbers.rotate_left(n.num as usize % (l - 1));
+    // first k nodes go left, the rest right
+    fn split(&mut self, t: usize, k: usize) -> (usize, usize) {
+        if t == NIL {
+            return (NIL, NIL);
+        }
+
+        let l = self.size(self.left[t]);
+
+        if k <= l {
+            let (a, b) = self.split(self.left[t], k);
+            self.left[t] = b;
+            self.update(t);
+            self.parent[t] = NIL;
+            if a != NIL {
+                self.parent[a] = NIL;
             }
-            numbers.push(n);
+            (a, t)
+        } else {
+            let (a, b) = self.split(self.right[t], k - l - 1);
+            self.right[t] = a;
+            self.update(t);
+            self.parent[t] = NIL;
+            if b != NIL {
+                self.parent[b] = NIL;
+            }
+            (t, b)
         }
     }
 
+    fn position(&self, mut t: usize) -> usize {
+        let mut pos = self.size(self.left[t]);
+
+        while self.parent[t] != NIL {
+            let p = self.parent[t];
+            if self.right[p] == t {
+                pos += self.size(self.left[p]) + 1;
+            }
+            t = p;
+        }
+
+        pos
+    }
+
+    fn remove_at(&mut self, pos: usize) -> usize {
+        let (a, b) = self.split(self.root, pos);
+        let (m, c) = self.split(b, 1);
+        self.root = self.merge(a, c);
+
+        m
+    }
+
+    fn insert_at(&mut self, pos: usize, node: usize) {
+        let (a, b) = self.split(self.root, pos);
+        let a = self.merge(a, node);
+        self.root = self.merge(a, b);
+    }
+
+    fn in_order(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut stack = vec![];
+        let mut t = self.root;
+
+        while t != NIL || !stack.is_empty() {
+            while t != NIL {
+                stack.push(t);
+                t = self.left[t];
+            }
+            t = stack.pop().unwrap();
+            order.push(t);
+            t = self.right[t];
+        }
+
+        order
+    }
+}
+
+// small deterministic generator for treap priorities and test inputs
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn main() -> Result<()> {
+    let mut numbers: Vec<i64> = vec![];
+
+    for line in io::stdin().lock().lines() {
+        numbers.push(line?.parse()?);
+    }
+
+    println!("Part 1: {}", grove(&mix(&numbers, 1, 1))?);
+
+    println!("Part 2: {}", grove(&mix(&numbers, ENCRYPTION_KEY, 10))?);
+
+    Ok(())
+}
+
+fn grove(numbers: &[i64]) -> Result<i64> {
     let pos0 = numbers
         .iter()
-        .position(|e| e.num == 0)
+        .position(|&e| e == 0)
         .ok_or(anyhow!("missing zero"))?;
 
     let sum: i64 = [1000, 2000, 3000]
         .iter()
-        .map(|x| numbers[(pos0 + x) % l].num)
+        .map(|x| numbers[(pos0 + x) % numbers.len()])
         .sum();
 
     Ok(sum)
 }
+
+fn mix(numbers: &[i64], key: i64, n: usize) -> Vec<i64> {
+    let nums: Vec<i64> = numbers.iter().map(|n| n * key).collect();
+
+    let l = nums.len();
+    let mut treap = Treap::new(l);
+
+    for _ in 0..n {
+        for (idx, &num) in nums.iter().enumerate() {
+            let pos = treap.position(idx);
+            treap.remove_at(pos);
+
+            let new_pos = (pos as i64 + num).rem_euclid(l as i64 - 1) as usize;
+            treap.insert_at(new_pos, idx);
+        }
+    }
+
+    treap.in_order().iter().map(|&i| nums[i]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy)]
+    struct Node {
+        num: i64,
+        idx: usize,
+    }
+
+    // original O(n^2) mixing, kept as a reference for the treap
+    fn mix_vec(numbers: &[Node], key: i64, n: usize) -> Result<Vec<i64>> {
+        let mut numbers: Vec<_> = numbers
+            .iter()
+            .map(|n| Node {
+                num: n.num * key,
+                idx: n.idx,
+            })
+            .collect();
+
+        let l = numbers.len();
+
+        for _ in 0..n {
+            for idx in 0..l {
+                // find pos
+                let pos = numbers
+                    .iter()
+                    .position(|n| n.idx == idx)
+                    .ok_or(anyhow!("missing index"))?;
+
+                // rotate to end
+                numbers.rotate_right(l - pos - 1);
+
+                // pop number
+                let n = numbers.pop().ok_or(anyhow!("no numbers"))?;
+
+                // rotate idx to last
+                if n.num < 0 {
+                    numbers.rotate_right(n.num.unsigned_abs() as usize % (l - 1));
+                } else if n.num > 0 {
+                    numbers.rotate_left(n.num as usize % (l - 1));
+                }
+                numbers.push(n);
+            }
+        }
+
+        Ok(numbers.iter().map(|n| n.num).collect())
+    }
+
+    // the lists are circular so compare them starting from the same number
+    fn normalise(numbers: &[i64], first: i64) -> Vec<i64> {
+        let mut numbers = numbers.to_vec();
+        if let Some(p) = numbers.iter().position(|&n| n == first) {
+            numbers.rotate_left(p);
+        }
+
+        numbers
+    }
+
+    #[test]
+    fn treap_matches_vec_rotation() {
+        let mut rng = XorShift(0x9e3779b97f4a7c15);
+
+        for case in 0..500 {
+            let l = 2 + rng.next() as usize % 50;
+
+            // unique values so normalising on the first number is unambiguous
+            let mut numbers: Vec<Node> = vec![];
+            while numbers.len() < l {
+                let num = (rng.next() % 2001) as i64 - 1000;
+                if numbers.iter().all(|n| n.num != num) {
+                    numbers.push(Node {
+                        num,
+                        idx: numbers.len(),
+                    });
+                }
+            }
+
+            for (key, rounds) in [(1, 1), (ENCRYPTION_KEY, 10)] {
+                let first = numbers[0].num * key;
+                let expected = normalise(&mix_vec(&numbers, key, rounds).unwrap(), first);
+                let nums: Vec<i64> = numbers.iter().map(|n| n.num).collect();
+                let actual = normalise(&mix(&nums, key, rounds), first);
+
+                assert_eq!(expected, actual, "case {} {:?}", case, numbers);
+            }
+        }
+    }
+}
@@ -12,11 +12,109 @@ fn main() -> Result<()> {
         lines.push(line?);
     }
 
-    let seeds = lines[0]
+    let (seeds, almanac) = parse(&lines)?;
+
+    let mut locations = vec![];
+    for &seed in &seeds {
+        locations.push(al(seed, &almanac));
+    }
+
+    println!(
+        "Part 1: {}",
+        locations.iter().min().ok_or(anyhow!("empty locations"))?
+    );
+
+    let seed_ranges: Vec<(u64, u64)> = seeds.chunks(2).map(|v| (v[0], v[0] + v[1])).collect();
+
+    let part2 = al_ranges(&seed_ranges, &almanac)
+        .iter()
+        .map(|r| r.0)
+        .min()
+        .ok_or(anyhow!("empty locations"))?;
+
+    println!("Part 2: {}", part2);
+
+    // cross check against mapping every seed individually. runs in 1m35s on
+    // my desktop for the full input so only use this on small inputs
+    if std::env::args().any(|a| a == "--brute") {
+        let brute = brute_force(&seed_ranges, &almanac);
+        println!("Part 2 (brute force): {}", brute);
+        if brute != part2 {
+            return Err(anyhow!("range mapping disagrees with brute force"));
+        }
+    }
+
+    Ok(())
+}
+
+fn al(mut seed: u64, almanac: &Almanac) -> u64 {
+    // dest, source, len
+    'outer: for ranges in almanac {
+        for range in ranges {
+            if seed >= range.1 && seed < (range.1 + range.2) {
+                seed = range.0 + (seed - range.1);
+                continue 'outer;
+            }
+        }
+    }
+
+    seed
+}
+
+// maps half open [start, end) ranges through each map in turn. A range that
+// partially overlaps a mapping is split, the overlap is shifted and the
+// untouched remainders are checked against the other mappings
+fn al_ranges(seeds: &[(u64, u64)], almanac: &Almanac) -> Vec<(u64, u64)> {
+    // empty ranges hold no seeds, don't let their start become a location
+    let mut current: Vec<_> = seeds.iter().filter(|r| r.0 < r.1).cloned().collect();
+
+    for ranges in almanac {
+        let mut mapped = vec![];
+
+        while let Some((start, end)) = current.pop() {
+            let overlap = ranges
+                .iter()
+                .find(|&&(_, src, len)| start < src + len && end > src);
+
+            match overlap {
+                Some(&(dst, src, len)) => {
+                    let lo = start.max(src);
+                    let hi = end.min(src + len);
+                    mapped.push((dst + (lo - src), dst + (hi - src)));
+
+                    if start < lo {
+                        current.push((start, lo));
+                    }
+                    if hi < end {
+                        current.push((hi, end));
+                    }
+                }
+                None => mapped.push((start, end)),
+            }
+        }
+
+        current = mapped;
+    }
+
+    current
+}
+
+fn brute_force(seeds: &[(u64, u64)], almanac: &Almanac) -> u64 {
+    seeds
+        .iter()
+        .flat_map(|&(start, end)| start..end)
+        .map(|seed| al(seed, almanac))
+        .min()
+        .unwrap_or(u64::MAX)
+}
+
+fn parse(lines: &[String]) -> Result<(Vec<u64>, Almanac)> {
+    let seeds = lines
+        .first()
+        .ok_or(anyhow!("missing seeds"))?
         .split(':')
         .nth(1)
         .ok_or(anyhow!("error parsing seed"))?
-        .trim()
         .split_whitespace()
         .map(|x| x.parse::<u64>())
         .collect::<Result<Vec<_>, _>>()?;
@@ -26,7 +124,7 @@ fn main() -> Result<()> {
 
     // line by line parser
     for line in &lines[1..] {
-        if line == "" {
+        if line.is_empty() {
             continue;
         }
 
@@ -50,44 +148,105 @@ fn main() -> Result<()> {
 
     almanac.push(ranges.clone());
 
-    let mut locations = vec![];
-    for &seed in &seeds {
-        locations.push(al(seed, &almanac));
+    Ok((seeds, almanac))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    fn example() -> Almanac {
+        let lines: Vec<String> = EXAMPLE.lines().map(String::from).collect();
+        parse(&lines).unwrap().1
     }
 
-    println!(
-        "Part 1: {}",
-        locations.iter().min().ok_or(anyhow!("empty locations"))?
-    );
+    fn lowest(seeds: &[(u64, u64)], almanac: &Almanac) -> u64 {
+        al_ranges(seeds, almanac)
+            .iter()
+            .map(|r| r.0)
+            .min()
+            .unwrap_or(u64::MAX)
+    }
 
-    let mut part2 = u64::MAX;
+    #[test]
+    fn example_matches_brute_force() {
+        let almanac = example();
+        let seeds = [(79, 93), (55, 68)];
 
-    // part 2 brute force solution, runs in 1m35s on my desktop
-    // Proper solution would be to rewrite al() to take a range
-    // and then split the range into sub rnages that overlap the
-    // next map's ranges, etc. Then return the minimum of the ranges
-    // Need to take into account ranges that don't overlap
-    for v in seeds.chunks(2) {
-        for seed in v[0]..(v[0] + v[1]) {
-            part2 = std::cmp::min(al(seed, &almanac), part2);
-        }
+        assert_eq!(lowest(&seeds, &almanac), 46);
+        assert_eq!(brute_force(&seeds, &almanac), 46);
     }
 
-    println!("Part 2: {}", part2);
+    #[test]
+    fn empty_seed_range_ignored() {
+        let almanac = example();
+        let seeds = [(79, 93), (55, 68), (0, 0)];
 
-    Ok(())
-}
+        assert_eq!(lowest(&seeds, &almanac), brute_force(&seeds, &almanac));
+    }
 
-fn al(mut seed: u64, almanac: &Almanac) -> u64 {
-    // dest, source, len
-    'outer: for ranges in almanac {
-        for range in ranges {
-            if seed >= range.1 && seed < (range.1 + range.2) {
-                seed = range.0 + (seed - range.1);
-                continue 'outer;
+    #[test]
+    fn every_seed_range_matches_brute_force() {
+        let almanacs: Vec<Almanac> = vec![
+            example(),
+            // no mappings at all
+            vec![vec![]],
+            // a range straddling both ends of a mapping
+            vec![vec![(100, 10, 5)]],
+            // adjacent mappings swapping two blocks, then a shift
+            vec![vec![(5, 0, 5), (0, 5, 5)], vec![(20, 3, 4)]],
+            // mappings onto the same destination
+            vec![vec![(0, 10, 5), (0, 20, 5)], vec![(7, 0, 3), (1, 3, 1)]],
+        ];
+
+        for almanac in &almanacs {
+            for start in 0..30 {
+                for end in start..30 {
+                    let seeds = [(start, end), (end, end + 3)];
+                    assert_eq!(
+                        lowest(&seeds, almanac),
+                        brute_force(&seeds, almanac),
+                        "seeds {:?} almanac {:?}",
+                        seeds,
+                        almanac
+                    );
+                }
             }
         }
     }
-
-    seed
 }
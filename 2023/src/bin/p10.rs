@@ -1,5 +1,7 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use aoc_2021::polygon::Polygon;
 use itertools::iproduct;
+use std::fmt::Write;
 use std::io::{self, BufRead};
 
 type Grid = Vec<Vec<char>>;
@@ -16,10 +18,34 @@ const NE: char = 'L';
 const NW: char = 'J';
 const SW: char = '7';
 const SE: char = 'F';
-const GROUND: char = '.';
 const START: char = 'S';
 
+// svg cell size in pixels
+const CELL: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Loop,
+    Inside,
+    Outside,
+}
+
+// usage: p10 [--render] [--svg file]
+// --render redraws the maze with box drawing characters, the loop in heavy
+// lines, inside cells shaded and anything outside left as light pipes
 fn main() -> Result<()> {
+    let mut render = false;
+    let mut svg = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render" => render = true,
+            "--svg" => svg = Some(args.next().ok_or(anyhow!("missing svg file"))?),
+            _ => return Err(anyhow!("unknown option: {}", arg)),
+        }
+    }
+
     let (mut map, start) = parse()?;
 
     // patch pipe at start loc by brute forcing each possibility
@@ -36,41 +62,162 @@ fn main() -> Result<()> {
     // might need to handle if path is odd  length
     println!("Part 1: {}", pipes.len() / 2);
 
-    // remove any random pipe segments
-    for (y, x) in iproduct!(0..map.h, 0..map.w) {
-        if !pipes.contains(&(x, y)) {
-            map.grid[y][x] = GROUND;
+    // the loop is a lattice polygon through the centre of each pipe, the
+    // enclosed tiles are its interior points
+    let inside = Polygon::from_loop(&pipes).interior();
+
+    if render || svg.is_some() {
+        let cells = classify(&map, &pipes);
+        debug_assert_eq!(
+            cells
+                .iter()
+                .flatten()
+                .filter(|&&c| c == Cell::Inside)
+                .count() as i64,
+            inside
+        );
+
+        if render {
+            println!("{}", render_text(&map, &cells));
         }
-    }
 
-    let inside = iproduct!(0..map.h, 0..map.w)
-        .filter(|&(y, x)| map.grid[y][x] == GROUND && is_inside(&map, x, y))
-        .count();
+        if let Some(file) = svg {
+            std::fs::write(file, render_svg(&map, &cells, &pipes))?;
+        }
+    }
 
     println!("Part 2: {}", inside);
+
     Ok(())
 }
 
-// Use point in polygon algorithm by casting to outside the loop
-// and counting crossings. Keep track of first corner seen so
-// can tell by next corner if it's a crossing. e.g. 'L7' is a crossing
-// but 'LJ' is not. Cast ray along x as this is easiest with map
-// in row major format.
-fn is_inside(map: &Map, x: usize, y: usize) -> bool {
-    let mut crossings = 0;
-    let mut first = GROUND;
-
-    for &c in &map.grid[y][x..] {
-        match (c, first) {
-            (NS, _) => crossings += 1,
-            (NE | SE, _) => first = c,
-            (SW, NE) => crossings += 1,
-            (NW, SE) => crossings += 1,
-            _ => {}
+// Scan each row counting crossings of the loop. Only pipes joining north
+// toggle, so a run like L-7 crosses once while L-J doesn't cross at all.
+fn classify(map: &Map, pipes: &[(usize, usize)]) -> Vec<Vec<Cell>> {
+    let mut cells = vec![vec![Cell::Outside; map.w]; map.h];
+    for &(x, y) in pipes {
+        cells[y][x] = Cell::Loop;
+    }
+
+    for (y, row) in cells.iter_mut().enumerate() {
+        let mut inside = false;
+        for (x, cell) in row.iter_mut().enumerate() {
+            match *cell {
+                Cell::Loop if [NS, NE, NW].contains(&map.grid[y][x]) => inside = !inside,
+                Cell::Loop => {}
+                _ if inside => *cell = Cell::Inside,
+                _ => {}
+            }
         }
     }
 
-    crossings % 2 == 1
+    cells
+}
+
+fn glyph(pipe: char, heavy: bool) -> char {
+    match (pipe, heavy) {
+        (NS, false) => '│',
+        (EW, false) => '─',
+        (NE, false) => '└',
+        (NW, false) => '┘',
+        (SW, false) => '┐',
+        (SE, false) => '┌',
+        (NS, true) => '┃',
+        (EW, true) => '━',
+        (NE, true) => '┗',
+        (NW, true) => '┛',
+        (SW, true) => '┓',
+        (SE, true) => '┏',
+        _ => ' ',
+    }
+}
+
+fn render_text(map: &Map, cells: &[Vec<Cell>]) -> String {
+    (0..map.h)
+        .map(|y| {
+            (0..map.w)
+                .map(|x| match cells[y][x] {
+                    Cell::Loop => glyph(map.grid[y][x], true),
+                    Cell::Inside => '▒',
+                    Cell::Outside => glyph(map.grid[y][x], false),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// pipe ends as offsets from the cell centre, in half cells
+fn ends(pipe: char) -> &'static [(i64, i64)] {
+    match pipe {
+        NS => &[(0, -1), (0, 1)],
+        EW => &[(-1, 0), (1, 0)],
+        NE => &[(0, -1), (1, 0)],
+        NW => &[(0, -1), (-1, 0)],
+        SW => &[(0, 1), (-1, 0)],
+        SE => &[(0, 1), (1, 0)],
+        _ => &[],
+    }
+}
+
+// inside cells are filled, stray pipes drawn faintly and the loop as a
+// single closed path through the centre of each of its cells
+fn render_svg(map: &Map, cells: &[Vec<Cell>], pipes: &[(usize, usize)]) -> String {
+    let half = CELL as i64 / 2;
+    let centre = |x: usize, y: usize| ((x * CELL) as i64 + half, (y * CELL) as i64 + half);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+        map.w * CELL,
+        map.h * CELL
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+
+    for (y, x) in iproduct!(0..map.h, 0..map.w) {
+        match cells[y][x] {
+            Cell::Inside => {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{CELL}" height="{CELL}" fill="lightgreen"/>"#,
+                    x * CELL,
+                    y * CELL
+                );
+            }
+            Cell::Outside => {
+                let (cx, cy) = centre(x, y);
+                for (dx, dy) in ends(map.grid[y][x]) {
+                    let _ = writeln!(
+                        svg,
+                        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="lightgrey"/>"#,
+                        cx,
+                        cy,
+                        cx + dx * half,
+                        cy + dy * half
+                    );
+                }
+            }
+            Cell::Loop => {}
+        }
+    }
+
+    let points = pipes
+        .iter()
+        .map(|&(x, y)| {
+            let (cx, cy) = centre(x, y);
+            format!("{},{}", cx, cy)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let _ = writeln!(
+        svg,
+        r#"<polygon points="{}" fill="none" stroke="black" stroke-width="2"/>"#,
+        points
+    );
+    let _ = writeln!(svg, "</svg>");
+
+    svg
 }
 
 fn trace_loop(map: &Map, start: (usize, usize)) -> Vec<(usize, usize)> {
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use itertools::{iproduct, Itertools};
 use std::io::{self, BufRead};
 
@@ -15,68 +15,168 @@ struct Image {
 const EMPTY: char = '.';
 const GALAXY: char = '#';
 
+// default expansion factors for parts 1 and 2
+const FACTORS: [u64; 2] = [2, 1_000_000];
+
+// number of empty rows before each row and empty columns before each column,
+// with one extra entry so the count before the end is available too
+#[derive(Debug)]
+struct Expansion {
+    rows: Vec<u64>,
+    cols: Vec<u64>,
+}
+
+impl Expansion {
+    // position once every empty row and column is grown to factor lines
+    fn expand(&self, (x, y): Pt, factor: u64) -> (u64, u64) {
+        (
+            x as u64 + self.cols[x] * (factor - 1),
+            y as u64 + self.rows[y] * (factor - 1),
+        )
+    }
+}
+
+// usage: p11 [factor ..] [--stats] [--check]
+// each factor prints the distance sum for that expansion, --stats adds the
+// mean and furthest pair distance and --check compares against summing each
+// pair separately
 fn main() -> Result<()> {
+    let mut factors = vec![];
+    let mut stats = false;
+    let mut check = false;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--stats" => stats = true,
+            "--check" => check = true,
+            _ => match arg.parse::<u64>() {
+                Ok(f) if f > 0 => factors.push(f),
+                _ => return Err(anyhow!("invalid factor: {}", arg)),
+            },
+        }
+    }
+
     let image = parse()?;
 
-    // find rows and colums that have no GALAXIES.
-    let (empty_rows, empty_cols) = empties(&image);
+    let expansion = empties(&image);
 
     // find galaxies
     let galaxies: Vec<_> = iproduct!(0..image.w, 0..image.h)
         .filter(|&(x, y)| image.grid[y][x] == GALAXY)
         .collect();
 
-    for (idx, s) in [2, 1_000_000].into_iter().enumerate() {
-        let sum: u64 = galaxies
-            .iter()
-            .combinations(2)
-            .map(|v| distance(*v[0], *v[1], &empty_rows, &empty_cols, s))
-            .sum();
+    let labelled = factors.is_empty();
+    if labelled {
+        factors.extend(FACTORS);
+    }
 
-        println!("Part {}: {}", idx + 1, sum);
+    for (idx, &f) in factors.iter().enumerate() {
+        let sum = total_distance(&galaxies, &expansion, f);
+
+        if check {
+            let pairwise: u64 = galaxies
+                .iter()
+                .tuple_combinations()
+                .map(|(&g0, &g1)| distance(g0, g1, &expansion, f))
+                .sum();
+
+            if pairwise != sum {
+                return Err(anyhow!(
+                    "factor {}: sorted {} pairwise {}",
+                    f,
+                    sum,
+                    pairwise
+                ));
+            }
+        }
+
+        if labelled {
+            println!("Part {}: {}", idx + 1, sum);
+        } else {
+            println!("Factor {}: {}", f, sum);
+        }
+
+        if stats {
+            let pairs = (galaxies.len() * galaxies.len().saturating_sub(1) / 2) as u64;
+            println!(
+                "  {} galaxies, {} pairs, mean {:.2}, furthest {}",
+                galaxies.len(),
+                pairs,
+                sum as f64 / pairs.max(1) as f64,
+                furthest(&galaxies, &expansion, f)
+            );
+        }
     }
 
     Ok(())
 }
 
-fn distance(g0: Pt, g1: Pt, empty_rows: &[usize], empty_cols: &[usize], size: u64) -> u64 {
-    use std::cmp::{max, min};
+fn distance(g0: Pt, g1: Pt, expansion: &Expansion, factor: u64) -> u64 {
+    let (x0, y0) = expansion.expand(g0, factor);
+    let (x1, y1) = expansion.expand(g1, factor);
 
-    let mut dist = 0;
+    x0.abs_diff(x1) + y0.abs_diff(y1)
+}
 
-    let rows = (min(g0.1, g1.1), max(g0.1, g1.1));
-    let cols = (min(g0.0, g1.0), max(g0.0, g1.0));
+// Manhattan distance splits into independent x and y sums. With the values
+// sorted each one is larger than all those before it, so it contributes
+// v * i - (sum of the previous i values).
+fn total_distance(galaxies: &[Pt], expansion: &Expansion, factor: u64) -> u64 {
+    let (mut xs, mut ys): (Vec<u64>, Vec<u64>) = galaxies
+        .iter()
+        .map(|&g| expansion.expand(g, factor))
+        .unzip();
+
+    [&mut xs, &mut ys]
+        .into_iter()
+        .map(|vs| {
+            vs.sort_unstable();
+
+            let mut prefix = 0;
+            let mut total = 0;
+            for (i, &v) in vs.iter().enumerate() {
+                total += v * i as u64 - prefix;
+                prefix += v;
+            }
+
+            total
+        })
+        .sum()
+}
 
-    for (range, empty) in &[(rows, empty_rows), (cols, empty_cols)] {
-        dist += range.1 as u64 - range.0 as u64;
+// largest Manhattan distance, the widest spread of x + y or x - y
+fn furthest(galaxies: &[Pt], expansion: &Expansion, factor: u64) -> u64 {
+    let rotated: Vec<(i64, i64)> = galaxies
+        .iter()
+        .map(|&g| {
+            let (x, y) = expansion.expand(g, factor);
+            ((x + y) as i64, x as i64 - y as i64)
+        })
+        .collect();
 
-        dist += empty
-            .iter()
-            .filter(|&r| r > &range.0 && r < &range.1)
-            .count() as u64
-            * (size - 1);
-    }
+    let spread = |vs: Vec<i64>| match vs.iter().minmax().into_option() {
+        Some((lo, hi)) => (hi - lo) as u64,
+        None => 0,
+    };
 
-    dist
+    spread(rotated.iter().map(|r| r.0).collect()).max(spread(rotated.iter().map(|r| r.1).collect()))
 }
 
-fn empties(image: &Image) -> (Vec<usize>, Vec<usize>) {
-    let mut rows = vec![];
-    let mut cols = vec![];
+fn empties(image: &Image) -> Expansion {
+    let mut rows = vec![0; image.h + 1];
+    let mut cols = vec![0; image.w + 1];
 
     for y in 0..image.h {
-        if image.grid[y].iter().all(|&c| c == EMPTY) {
-            rows.push(y);
-        }
+        let empty = image.grid[y].iter().all(|&c| c == EMPTY);
+        rows[y + 1] = rows[y] + empty as u64;
     }
 
     for x in 0..image.w {
-        if (0..image.h).map(|y| image.grid[y][x]).all(|c| c == EMPTY) {
-            cols.push(x);
-        }
+        let empty = (0..image.h).map(|y| image.grid[y][x]).all(|c| c == EMPTY);
+        cols[x + 1] = cols[x] + empty as u64;
     }
 
-    (rows, cols)
+    Expansion { rows, cols }
 }
 
 fn parse() -> Result<Image> {
@@ -1,37 +1,75 @@
-use anyhow::Result;
-use itertools::Itertools;
-use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use rand::Rng;
 use std::io::{self, BufRead};
 
-const OPERATIONAL: char = '.';
-const DAMAGED: char = '#';
-const UNKNOWN: char = '?';
+const OPERATIONAL: u8 = b'.';
+const DAMAGED: u8 = b'#';
+const UNKNOWN: u8 = b'?';
 
-type Cache = HashMap<(String, Vec<u64>), u64>;
-fn main() -> Result<()> {
-    let rows = parse()?;
-
-    let mut cache: Cache = HashMap::new();
+const UNFOLD: usize = 5;
 
-    let p1: u64 = rows.iter().map(|r| arrange(&r.0, &r.1, &mut cache)).sum();
+type Row = (Vec<u8>, Vec<usize>);
 
-    println!("Part 1: {}", p1);
+// usage: p12 [--unfold k] [--list row] [--sample row n]
+// --list prints every arrangement of a row (1 based) and --sample picks n
+// uniformly at random, both use the row as is unless --unfold is given
+fn main() -> Result<()> {
+    let mut unfold = None;
+    let mut list = None;
+    let mut sample = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || -> Result<usize> {
+            let v = args.next().ok_or(anyhow!("missing value for {}", arg))?;
+            Ok(v.parse()?)
+        };
+
+        match arg.as_str() {
+            "--unfold" => unfold = Some(value()?),
+            "--list" => list = Some(value()?),
+            "--sample" => sample = Some((value()?, value()?)),
+            _ => return Err(anyhow!("unknown option: {}", arg)),
+        }
+    }
 
-    let mut unfolded = vec![];
-    for (pattern, counts) in &rows {
-        let pattern_unfolded: String = std::iter::repeat(pattern.clone()).take(5).join("?");
+    let rows = parse()?;
 
-        let counts_unfolded: Vec<u64> = std::iter::repeat(counts.clone())
-            .take(5)
-            .flatten()
-            .collect::<Vec<_>>();
+    let mut counter = Counter::default();
+
+    if list.is_some() || sample.is_some() {
+        let row = list.or(sample.map(|s| s.0)).unwrap_or(0);
+        let (pattern, groups) = rows
+            .get(row.wrapping_sub(1))
+            .map(|r| unfolded(r, unfold.unwrap_or(1)))
+            .ok_or(anyhow!("no such row: {}", row))?;
+
+        println!("{} arrangements", counter.count(&pattern, &groups));
+
+        match sample {
+            Some((_, n)) => {
+                for _ in 0..n {
+                    if let Some(a) = counter.sample(&pattern, &groups) {
+                        println!("{}", String::from_utf8_lossy(&a));
+                    }
+                }
+            }
+            None => counter.list(&pattern, &groups, 0, 0, &mut pattern.clone()),
+        }
 
-        unfolded.push((pattern_unfolded, counts_unfolded));
+        return Ok(());
     }
 
-    let p2: u64 = unfolded
+    let p1: u64 = rows.iter().map(|r| counter.count(&r.0, &r.1)).sum();
+
+    println!("Part 1: {}", p1);
+
+    let p2: u64 = rows
         .iter()
-        .map(|r| arrange(&r.0, &r.1, &mut cache))
+        .map(|r| {
+            let (pattern, groups) = unfolded(r, unfold.unwrap_or(UNFOLD));
+            counter.count(&pattern, &groups)
+        })
         .sum();
 
     println!("Part 2: {}", p2);
@@ -39,100 +77,176 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn arrange(pattern: &str, groups: &[u64], cache: &mut Cache) -> u64 {
-    if let Some(x) = cache.get(&(pattern.to_owned(), groups.to_vec())) {
-        return *x;
+fn unfolded((pattern, groups): &Row, factor: usize) -> Row {
+    let mut p = vec![];
+    for i in 0..factor {
+        if i > 0 {
+            p.push(UNKNOWN);
+        }
+        p.extend_from_slice(pattern);
     }
 
-    // ==================== Terminal Cases ====================
+    (p, groups.repeat(factor))
+}
 
-    // if we are out of groups this might be still a vaild pattern if none of
-    // the remaining springs are DAMAGED otherwise it's not valid
-    // [..?] () is valid, [..#.] () is invalid
-    if groups.len() == 0 {
-        if pattern.chars().all(|s| s != DAMAGED) {
-            return 1;
-        } else {
-            return 0;
-        }
+// Bottom up count of arrangements. ways(i, g) is the number of ways to fill
+// pattern[i..] with groups[g..], built from the end of the pattern back to
+// the start. The buffers are kept between rows so counting doesn't allocate
+// once they are big enough.
+#[derive(Debug, Default)]
+struct Counter {
+    // (pattern.len() + 1) x (groups.len() + 1), row major by position
+    ways: Vec<u64>,
+    // number of non operational springs in a row starting at each position
+    run: Vec<usize>,
+    stride: usize,
+}
+
+impl Counter {
+    fn ways(&self, i: usize, g: usize) -> u64 {
+        self.ways[i * self.stride + g]
     }
 
-    // if we have one group left which is the same length as our pattern
-    // and the pattern contains no OPERATIONAL springs this is a valid
-    // pattern otherwise it's not valid
-    // [##?#] (4) is valid, [##.#] (4) is invalid
-    if groups.len() == 1 && pattern.len() == groups[0] as usize {
-        if pattern.chars().all(|s| s != OPERATIONAL) {
-            return 1;
-        } else {
-            return 0;
+    // can groups[g] start at position i, if so where does the next group
+    // search start. A group must be followed by the end or a possible gap
+    fn place(&self, pattern: &[u8], groups: &[usize], i: usize, g: usize) -> Option<usize> {
+        let n = pattern.len();
+        let len = *groups.get(g)?;
+
+        if pattern[i] == OPERATIONAL || self.run[i] < len {
+            return None;
         }
-    }
 
-    // if the sum of the remaining groups is greater than the pattern length
-    // the groups can not fit and this pattern is invalid
-    if groups.iter().sum::<u64>() > pattern.len() as u64 {
-        return 0;
+        match pattern.get(i + len) {
+            None => Some(n),
+            Some(&DAMAGED) => None,
+            Some(_) => Some(i + len + 1),
+        }
     }
 
-    // ==================== Recursive Cases ====================
+    fn count(&mut self, pattern: &[u8], groups: &[usize]) -> u64 {
+        let (n, m) = (pattern.len(), groups.len());
+
+        self.stride = m + 1;
+        self.ways.clear();
+        self.ways.resize((n + 1) * self.stride, 0);
+        self.run.clear();
+        self.run.resize(n + 1, 0);
+
+        for i in (0..n).rev() {
+            self.run[i] = if pattern[i] == OPERATIONAL {
+                0
+            } else {
+                self.run[i + 1] + 1
+            };
+        }
+
+        // only an empty pattern with no groups left is valid at the end
+        self.ways[n * self.stride + m] = 1;
+
+        for i in (0..n).rev() {
+            for g in 0..=m {
+                let mut w = 0;
+
+                // treat it as operational and skip
+                if pattern[i] != DAMAGED {
+                    w += self.ways(i + 1, g);
+                }
 
-    // skip operation springs until we find a potential starting location for
-    // the next group
-    if let Some(OPERATIONAL) = pattern.chars().nth(0) {
-        return arrange(&pattern[1..], groups, cache);
+                // treat it as damaged, the start of the next group
+                if let Some(next) = self.place(pattern, groups, i, g) {
+                    w += self.ways(next, g + 1);
+                }
+
+                self.ways[i * self.stride + g] = w;
+            }
+        }
+
+        self.ways(0, 0)
     }
 
-    // if current spring is unknown we have two potential cases
-
-    // 1: treat it as operational and skip as above
-    let skip_cases = if let Some(UNKNOWN) = pattern.chars().nth(0) {
-        arrange(&pattern[1..], groups, cache)
-    } else {
-        0
-    };
-
-    // 2: treat it as damaged and potentially part of the next group
-
-    // if any springs in the next potential group are operational, cache and
-    // return the skip_cases only
-    if pattern[..groups[0] as usize]
-        .chars()
-        .any(|c| c == OPERATIONAL)
-    {
-        cache.insert((pattern.to_owned(), groups.to_vec()), skip_cases);
-        return skip_cases;
+    // walk the table built by count, following only branches with
+    // arrangements so every leaf is valid
+    fn list(&self, pattern: &[u8], groups: &[usize], i: usize, g: usize, cur: &mut Vec<u8>) {
+        if i >= pattern.len() {
+            if g == groups.len() {
+                println!("{}", String::from_utf8_lossy(cur));
+            }
+            return;
+        }
+
+        if pattern[i] != DAMAGED && self.ways(i + 1, g) > 0 {
+            cur[i] = OPERATIONAL;
+            self.list(pattern, groups, i + 1, g, cur);
+        }
+
+        if let Some(next) = self.place(pattern, groups, i, g) {
+            if self.ways(next, g + 1) > 0 {
+                let end = i + groups[g];
+                cur[i..end].fill(DAMAGED);
+                if end < pattern.len() {
+                    cur[end] = OPERATIONAL;
+                }
+                self.list(pattern, groups, next, g + 1, cur);
+            }
+        }
+
+        cur[i..].copy_from_slice(&pattern[i..]);
     }
 
-    // if the next spring after the current group is not damaged, this is a valid
-    // pattern, move forward to the next group
-    let match_cases = if let Some(OPERATIONAL | UNKNOWN) = pattern.chars().nth(groups[0] as usize) {
-        arrange(&pattern[(groups[0] as usize + 1)..], &groups[1..], cache)
-    } else {
-        0
-    };
+    // uniform random arrangement, each choice is weighted by the number of
+    // arrangements below it
+    fn sample(&self, pattern: &[u8], groups: &[usize]) -> Option<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        let mut cur = pattern.to_vec();
+        let (mut i, mut g) = (0, 0);
 
-    cache.insert(
-        (pattern.to_owned(), groups.to_vec()),
-        skip_cases + match_cases,
-    );
+        if self.ways(0, 0) == 0 {
+            return None;
+        }
+
+        while i < pattern.len() {
+            let skip = if pattern[i] != DAMAGED {
+                self.ways(i + 1, g)
+            } else {
+                0
+            };
+            let place = self.place(pattern, groups, i, g);
+            let take = place.map_or(0, |next| self.ways(next, g + 1));
+
+            if rng.gen_range(0..skip + take) < skip {
+                cur[i] = OPERATIONAL;
+                i += 1;
+            } else {
+                let end = i + groups[g];
+                cur[i..end].fill(DAMAGED);
+                if end < pattern.len() {
+                    cur[end] = OPERATIONAL;
+                }
+                i = place?;
+                g += 1;
+            }
+        }
 
-    skip_cases + match_cases
+        Some(cur)
+    }
 }
 
-fn parse() -> Result<Vec<(String, Vec<u64>)>> {
-    let mut rows: Vec<(String, Vec<u64>)> = vec![];
+fn parse() -> Result<Vec<Row>> {
+    let mut rows: Vec<Row> = vec![];
     for line in io::stdin().lock().lines() {
         let line = line?;
 
-        let left_right: Vec<_> = line.split_whitespace().collect();
+        let (pattern, groups) = line
+            .split_once(' ')
+            .ok_or(anyhow!("invalid row: {}", line))?;
 
         rows.push((
-            left_right[0].chars().collect(),
-            left_right[1]
+            pattern.as_bytes().to_vec(),
+            groups
                 .split(',')
                 .map(|s| s.parse())
-                .collect::<Result<Vec<u64>, _>>()?,
+                .collect::<Result<Vec<usize>, _>>()?,
         ));
     }
 
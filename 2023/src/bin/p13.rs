@@ -1,96 +1,114 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::io::{self, Read};
 
 const ASH: char = '.';
 const ROCK: char = '#';
 
+// each row and column packed into a bitmask, bit n set for rock at n
 #[derive(Debug, Clone)]
 struct Map {
-    grid: Vec<Vec<char>>,
-    w: usize,
-    h: usize,
+    rows: Vec<u64>,
+    cols: Vec<u64>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Horizontal, // between rows
+    Vertical,   // between columns
+}
+
+#[derive(Debug, Clone)]
+struct Reflection {
+    axis: Axis,
+    // number of rows above or columns left of the line
+    index: usize,
+    // (x, y) of each differing cell on the top or left side of the line
+    defects: Vec<(usize, usize)>,
+}
+
+impl Reflection {
+    fn score(&self) -> usize {
+        match self.axis {
+            Axis::Horizontal => self.index * 100,
+            Axis::Vertical => self.index,
+        }
+    }
+}
+
+// usage: p13 [--show], --show lists every reflection line and its defects
 fn main() -> Result<()> {
+    let show = std::env::args().any(|a| a == "--show");
+
     let maps: Vec<Map> = parse()?;
 
-    // part 1
-    println!(
-        "Part 1: {}",
-        maps.iter().map(|m| score_map(m)).flatten().sum::<usize>()
-    );
-
-    // part 2
-    let mut sum = 0;
-
-    for map in &maps {
-        //calc original score
-        let original = score_map(&map);
-
-        'outer: for y in 0..map.h {
-            for x in 0..map.w {
-                // clone and tweak
-                let mut m = map.clone();
-
-                m.grid[y][x] = match m.grid[y][x] {
-                    ROCK => ASH,
-                    ASH => ROCK,
-                    _ => unreachable!(),
-                };
-
-                let new_score = score_map(&m);
-                if new_score.len() > 0 && new_score != original {
-                    sum += new_score
-                        .iter()
-                        .filter(|x| !original.contains(x))
-                        .sum::<usize>();
-                    break 'outer;
+    if show {
+        for (i, map) in maps.iter().enumerate() {
+            for k in [0, 1] {
+                for r in reflections(map, k) {
+                    println!(
+                        "map {} k {}: {:?} line after {} defects {:?}",
+                        i + 1,
+                        k,
+                        r.axis,
+                        r.index,
+                        r.defects
+                    );
                 }
             }
         }
     }
 
-    println!("Part 2: {}", sum);
+    println!("Part 1: {}", score(&maps, 0));
+
+    println!("Part 2: {}", score(&maps, 1));
 
     Ok(())
 }
 
-// fairly inefficient but it works
-fn score_map(map: &Map) -> Vec<usize> {
-    let mut lines = vec![];
-
-    let rows: Vec<String> = map.grid.iter().map(|r| r.iter().collect()).collect();
-
-    let cols: Vec<String> = (0..map.w)
-        .map(|x| (0..map.h).map(|y| map.grid[y][x]).collect())
-        .collect();
-
-    for y in 1..(map.h) {
-        if is_mirror(y, &rows) {
-            lines.push(y * 100);
-        }
-    }
-
-    for x in 1..(map.w) {
-        if is_mirror(x, &cols) {
-            lines.push(x);
-        };
-    }
-
-    lines
+fn score(maps: &[Map], defects: usize) -> usize {
+    maps.iter()
+        .flat_map(|m| reflections(m, defects))
+        .map(|r| r.score())
+        .sum()
 }
 
-fn is_mirror(idx: usize, lines: &[String]) -> bool {
-    let top = 0..idx;
-    let bottom = idx..lines.len();
+// every reflection line that needs exactly k cells changed to be a mirror
+fn reflections(map: &Map, k: usize) -> Vec<Reflection> {
+    let rows = mirrors(&map.rows, k).map(|(index, defects)| Reflection {
+        axis: Axis::Horizontal,
+        index,
+        defects,
+    });
+
+    // column masks are indexed by y so swap to keep defects as (x, y)
+    let cols = mirrors(&map.cols, k).map(|(index, defects)| Reflection {
+        axis: Axis::Vertical,
+        index,
+        defects: defects.into_iter().map(|(y, x)| (x, y)).collect(),
+    });
+
+    rows.chain(cols).collect()
+}
 
-    for p in std::iter::zip(top.rev(), bottom) {
-        if lines[p.0] != lines[p.1] {
-            return false;
+// (index, (bit, line) of each differing cell) for lines mirrored after index
+fn mirrors(lines: &[u64], k: usize) -> impl Iterator<Item = (usize, Vec<(usize, usize)>)> + '_ {
+    (1..lines.len()).filter_map(move |idx| {
+        let pairs = std::iter::zip((0..idx).rev(), idx..lines.len());
+
+        let mut defects = vec![];
+        for (a, b) in pairs {
+            let mut diff = lines[a] ^ lines[b];
+            while diff != 0 {
+                defects.push((diff.trailing_zeros() as usize, a));
+                if defects.len() > k {
+                    return None;
+                }
+                diff &= diff - 1;
+            }
         }
-    }
 
-    true
+        (defects.len() == k).then_some((idx, defects))
+    })
 }
 
 fn parse() -> Result<Vec<Map>> {
@@ -100,13 +118,32 @@ fn parse() -> Result<Vec<Map>> {
     let mut maps = vec![];
 
     for m in buffer.split("\n\n") {
-        let mut grid: Vec<Vec<char>> = vec![];
-        for r in m.trim().split('\n') {
-            grid.push(r.chars().collect());
-        }
+        let grid: Vec<Vec<char>> = m.trim().split('\n').map(|r| r.chars().collect()).collect();
+
         let w = grid[0].len();
         let h = grid.len();
-        maps.push(Map { grid, w, h });
+
+        if w > 64 || h > 64 {
+            return Err(anyhow!("map too large for u64 masks: {}x{}", w, h));
+        }
+
+        let mut rows = vec![0; h];
+        let mut cols = vec![0; w];
+
+        for (y, row) in grid.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                match c {
+                    ROCK => {
+                        rows[y] |= 1 << x;
+                        cols[x] |= 1 << y;
+                    }
+                    ASH => {}
+                    _ => return Err(anyhow!("unknown tile: {}", c)),
+                }
+            }
+        }
+
+        maps.push(Map { rows, cols });
     }
 
     Ok(maps)
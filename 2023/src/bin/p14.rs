@@ -1,105 +1,268 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt;
 use std::io::{self, BufRead};
 
 const ROUND: char = 'O';
-const _ROCK: char = '#';
+const ROCK: char = '#';
 const EMPTY: char = '.';
 
+const CYCLES: usize = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tilt {
+    North,
+    West,
+    South,
+    East,
+}
+
+const CYCLE: [Tilt; 4] = [Tilt::North, Tilt::West, Tilt::South, Tilt::East];
+
+impl TryFrom<char> for Tilt {
+    type Error = anyhow::Error;
+
+    fn try_from(c: char) -> Result<Self> {
+        match c {
+            'N' => Ok(Tilt::North),
+            'W' => Ok(Tilt::West),
+            'S' => Ok(Tilt::South),
+            'E' => Ok(Tilt::East),
+            _ => Err(anyhow!("unknown tilt: {}", c)),
+        }
+    }
+}
+
+// one bit per cell, bit x of row y set for a rock at (x, y). Cube rocks never
+// move so each row's runs of free cells between them are worked out once
 #[derive(Debug, Clone)]
-struct Map {
-    grid: Vec<Vec<char>>,
+struct Platform {
+    round: Vec<u128>,
+    cube: Vec<u128>,
+    // (start, len) of each run of cells between cube rocks in each row
+    segments: Vec<Vec<(u32, u32)>>,
     w: usize,
     h: usize,
 }
 
-fn main() -> Result<()> {
-    let mut map: Map = parse()?;
+impl Platform {
+    fn new(round: Vec<u128>, cube: Vec<u128>, w: usize) -> Self {
+        let segments = cube
+            .iter()
+            .map(|&c| {
+                let mut segs = vec![];
+                let mut start = 0;
+                for x in 0..=w as u32 {
+                    if x == w as u32 || c & (1 << x) != 0 {
+                        if x > start {
+                            segs.push((start, x - start));
+                        }
+                        start = x + 1;
+                    }
+                }
+                segs
+            })
+            .collect();
 
-    // part 1
-    roll_north(&mut map);
+        Platform {
+            h: round.len(),
+            round,
+            cube,
+            segments,
+            w,
+        }
+    }
 
-    println!("Part 1: {}", load(&map));
+    fn tilt(&mut self, tilt: Tilt) {
+        match tilt {
+            Tilt::North => self.tilt_vertical((0..self.h).collect()),
+            Tilt::South => self.tilt_vertical((0..self.h).rev().collect()),
+            Tilt::West | Tilt::East => self.tilt_horizontal(tilt == Tilt::West),
+        }
+    }
 
-    // part 2
-    const CYCLES: usize = 1_000_000_000;
-    const HISTORY: usize = 500; // increase if not working for a certain input
-    const START: usize = HISTORY / 2;
+    // pack each run of round rocks against the cube rock (or edge) to the
+    // west or east of it
+    fn tilt_horizontal(&mut self, west: bool) {
+        for (row, segs) in self.round.iter_mut().zip(&self.segments) {
+            for &(start, len) in segs {
+                let mask = ones(len) << start;
+                let n = (*row & mask).count_ones();
+                let packed = if west {
+                    ones(n) << start
+                } else {
+                    ones(n) << (start + len - n)
+                };
+                *row = (*row & !mask) | packed;
+            }
+        }
+    }
 
-    let mut loads = vec![];
+    // visit rows in the direction of the tilt, tracking the next free row in
+    // each column so every rock moves once
+    fn tilt_vertical(&mut self, order: Vec<usize>) {
+        let mut free = vec![order[0]; self.w];
+        let step = |y: usize| {
+            if order[0] == 0 {
+                y + 1
+            } else {
+                y.wrapping_sub(1)
+            }
+        };
 
-    for _ in 0..HISTORY {
-        // one cycle
-        for _ in 0..4 {
-            roll_north(&mut map);
-            rotate(&mut map);
-        }
+        for &y in &order {
+            let mut cube = self.cube[y];
+            while cube != 0 {
+                let x = cube.trailing_zeros() as usize;
+                free[x] = step(y);
+                cube &= cube - 1;
+            }
 
-        loads.push(load(&map));
+            let mut round = self.round[y];
+            while round != 0 {
+                let x = round.trailing_zeros() as usize;
+                self.round[y] &= !(1 << x);
+                self.round[free[x]] |= 1 << x;
+                free[x] = step(free[x]);
+                round &= round - 1;
+            }
+        }
     }
 
-    for offset in 1.. {
-        if loads[START..START + 3] == loads[START + offset..START + offset + 3] {
-            println!("Part 2: {}", loads[START + ((CYCLES - START - 1) % offset)]);
-            break;
+    fn spin(&mut self) {
+        for tilt in CYCLE {
+            self.tilt(tilt);
         }
     }
 
-    Ok(())
+    fn load(&self) -> usize {
+        self.round
+            .iter()
+            .enumerate()
+            .map(|(y, r)| (self.h - y) * r.count_ones() as usize)
+            .sum()
+    }
 }
 
-fn load(map: &Map) -> usize {
-    (0..map.h)
-        .rev()
-        .enumerate()
-        .map(|(idx, y)| (idx + 1) * map.grid[y].iter().filter(|&&c| c == ROUND).count())
-        .sum::<usize>()
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.h {
+            for x in 0..self.w {
+                let c = if self.round[y] & (1 << x) != 0 {
+                    ROUND
+                } else if self.cube[y] & (1 << x) != 0 {
+                    ROCK
+                } else {
+                    EMPTY
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
 }
 
-fn roll_north(map: &mut Map) {
-    for y in 0..map.h {
-        for x in 0..map.w {
-            if map.grid[y][x] == ROUND {
-                let mut ny = y;
-                while ny > 0 && map.grid[ny - 1][x] == EMPTY {
-                    ny -= 1;
-                }
+fn ones(n: u32) -> u128 {
+    if n == 128 {
+        u128::MAX
+    } else {
+        (1 << n) - 1
+    }
+}
 
-                if ny != y {
-                    map.grid[y][x] = EMPTY;
-                    map.grid[ny][x] = ROUND;
-                }
-            }
+// usage: p14 [--tilts NWSE..], --tilts applies the given tilts in order and
+// prints the platform and load after each
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let tilts = match args.first().map(|s| s.as_str()) {
+        Some("--tilts") => {
+            let seq = args.get(1).ok_or(anyhow!("missing tilt sequence"))?;
+            Some(
+                seq.chars()
+                    .map(Tilt::try_from)
+                    .collect::<Result<Vec<_>>>()?,
+            )
         }
+        Some(a) => return Err(anyhow!("unknown option: {}", a)),
+        None => None,
+    };
+
+    let platform = parse()?;
+
+    if let Some(tilts) = tilts {
+        let mut p = platform;
+        for tilt in tilts {
+            p.tilt(tilt);
+            println!("== {:?} load {} ==\n{}", tilt, p.load(), p);
+        }
+        return Ok(());
     }
+
+    let mut p = platform.clone();
+    p.tilt(Tilt::North);
+
+    println!("Part 1: {}", p.load());
+
+    println!("Part 2: {}", spin_cycles(platform, CYCLES));
+
+    Ok(())
 }
 
-fn rotate(map: &mut Map) {
-    // transpose
-    for y in 0..map.h {
-        for x in y + 1..map.w {
-            let tmp = map.grid[y][x];
-            map.grid[y][x] = map.grid[x][y];
-            map.grid[x][y] = tmp;
+// spin until a layout repeats then skip ahead by whole loops
+fn spin_cycles(mut p: Platform, cycles: usize) -> usize {
+    let mut seen = HashMap::new();
+
+    let mut i = 0;
+    while i < cycles {
+        if let Some(prev) = seen.insert(p.round.clone(), i) {
+            let period = i - prev;
+            i += (cycles - i) / period * period;
+            seen.clear();
+            if i == cycles {
+                break;
+            }
         }
+
+        p.spin();
+        i += 1;
     }
-    // reverse rows
-    for y in 0..map.h {
-        map.grid[y].reverse();
-    }
+
+    p.load()
 }
-fn parse() -> Result<Map> {
-    let mut grid: Vec<_> = vec![];
+
+fn parse() -> Result<Platform> {
+    let mut round = vec![];
+    let mut cube = vec![];
+    let mut w = 0;
 
     for line in io::stdin().lock().lines() {
         let line = line?;
 
-        grid.push(line.chars().collect::<Vec<_>>());
-    }
+        w = line.len();
+        if w > 128 {
+            return Err(anyhow!("platform too wide for u128 rows: {}", w));
+        }
 
-    let w = grid[0].len();
-    let h = grid.len();
+        let (mut r, mut c) = (0u128, 0u128);
+        for (x, ch) in line.chars().enumerate() {
+            match ch {
+                ROUND => r |= 1 << x,
+                ROCK => c |= 1 << x,
+                EMPTY => {}
+                _ => return Err(anyhow!("unknown tile: {}", ch)),
+            }
+        }
 
-    let map = Map { grid, w, h };
+        round.push(r);
+        cube.push(c);
+    }
+
+    if round.is_empty() {
+        return Err(anyhow!("empty platform"));
+    }
 
-    Ok(map)
+    Ok(Platform::new(round, cube, w))
 }
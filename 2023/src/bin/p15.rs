@@ -1,10 +1,101 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
 use std::io::{self, Read};
 
-type Lense = (String, u32);
-type Box = Vec<Lense>;
+const BOXES: usize = 256;
 
+// The puzzle's HASHMAP. Keys go in the box picked by the HASH algorithm and
+// each box keeps its entries in insertion order, with replacing a value
+// keeping its original slot. Entries carry an insertion sequence number so
+// insert, replace and remove are plain hash map operations and the order is
+// only rebuilt when a box is read.
+#[derive(Debug, Clone)]
+struct Hashmap<K, V> {
+    boxes: Vec<HashMap<K, (u64, V)>>,
+    seq: u64,
+}
+
+impl<K: AsRef<str> + Hash + Eq, V> Hashmap<K, V> {
+    fn new() -> Self {
+        Hashmap {
+            boxes: (0..BOXES).map(|_| HashMap::new()).collect(),
+            seq: 0,
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        let b = &mut self.boxes[hash(key.as_ref()) as usize];
+
+        match b.get_mut(&key) {
+            Some(entry) => entry.1 = value,
+            None => {
+                b.insert(key, (self.seq, value));
+                self.seq += 1;
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.boxes[hash(key.as_ref()) as usize]
+            .remove(key)
+            .map(|(_, v)| v)
+    }
+
+    // entries of box n front to back
+    fn contents(&self, n: usize) -> Vec<(&K, &V)> {
+        let mut entries: Vec<_> = self.boxes[n].iter().collect();
+        entries.sort_by_key(|(_, (seq, _))| *seq);
+
+        entries.into_iter().map(|(k, (_, v))| (k, v)).collect()
+    }
+}
+
+impl<K: AsRef<str> + Hash + Eq, V: Copy + Into<u64>> Hashmap<K, V> {
+    // sum of box number * slot * focal length, both 1 based
+    fn focusing_power(&self) -> u64 {
+        (0..BOXES)
+            .flat_map(|n| {
+                self.contents(n)
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(slot, (_, &v))| (n as u64 + 1) * (slot as u64 + 1) * v.into())
+            })
+            .sum()
+    }
+}
+
+// non empty boxes as in the puzzle example, e.g. Box 0: [rn 1] [cm 2]
+impl<K: AsRef<str> + Hash + Eq, V: fmt::Display> fmt::Display for Hashmap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for n in 0..BOXES {
+            let contents = self.contents(n);
+            if contents.is_empty() {
+                continue;
+            }
+
+            write!(f, "Box {}:", n)?;
+            for (k, v) in contents {
+                write!(f, " [{} {}]", k.as_ref(), v)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Insert(String, u32),
+    Remove(String),
+}
+
+// usage: p15 [--verbose], --verbose prints the boxes after every step
 fn main() -> Result<()> {
+    let verbose = std::env::args().any(|a| a == "--verbose");
+
     let mut buffer = String::new();
     io::stdin().lock().read_to_string(&mut buffer)?;
 
@@ -14,44 +105,36 @@ fn main() -> Result<()> {
     println!("Part 1: {}", p1);
 
     // part 2
-    let mut boxes: Vec<Box> = vec![vec![]; 256];
-
-    // for each step
-    for step in buffer.trim().split(',') {
-        let label: String = step.chars().filter(|&c| c.is_alphabetic()).collect();
-        let box_num = hash(&label) as usize;
-
-        if let Some(s) = step.split('=').nth(1) {
-            // add to box
-            let lense_num: u32 = s.trim().parse()?;
-
-            if let Some(p) = boxes[box_num].iter().position(|l| l.0 == label) {
-                // update existing lense
-                boxes[box_num][p] = (label, lense_num);
-            } else {
-                // add to box at end
-                boxes[box_num].push((label, lense_num));
-            }
-        } else {
-            // remove from box if present
-            if let Some(p) = boxes[box_num].iter().position(|l| l.0 == label) {
-                boxes[box_num].remove(p);
+    let mut boxes: Hashmap<String, u32> = Hashmap::new();
+
+    for s in buffer.trim().split(',') {
+        match parse_step(s)? {
+            Step::Insert(label, focal) => boxes.insert(label, focal),
+            Step::Remove(label) => {
+                boxes.remove(&label);
             }
         }
-    }
 
-    let mut p2 = 0;
-    for (box_num, b) in boxes.iter().enumerate() {
-        for (lense_num, lense) in b.iter().enumerate() {
-            p2 += (box_num + 1) * (lense_num + 1) * lense.1 as usize;
+        if verbose {
+            println!("After \"{}\":\n{}", s, boxes);
         }
     }
 
-    println!("Part 2: {}", p2);
+    println!("Part 2: {}", boxes.focusing_power());
 
     Ok(())
 }
 
+fn parse_step(s: &str) -> Result<Step> {
+    if let Some((label, focal)) = s.split_once('=') {
+        Ok(Step::Insert(label.to_string(), focal.trim().parse()?))
+    } else if let Some(label) = s.strip_suffix('-') {
+        Ok(Step::Remove(label.to_string()))
+    } else {
+        Err(anyhow!("invalid step: {}", s))
+    }
+}
+
 // working in ascii so as_bytes() ok
 fn hash(s: &str) -> u8 {
     s.as_bytes()
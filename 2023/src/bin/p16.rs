@@ -1,5 +1,4 @@
 use anyhow::Result;
-use std::collections::HashSet;
 use std::io::{self, BufRead};
 
 const VERT_SPLIT: char = '|';
@@ -8,8 +7,8 @@ const MIRROR_F: char = '/';
 const MIRROR_B: char = '\\';
 const EMPTY: char = '.';
 
-type V = (i32, i32);
-type S = HashSet<(V, V)>;
+// right, down, left, up
+const DIRS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
 
 #[derive(Debug, Clone)]
 struct Map {
@@ -18,162 +17,281 @@ struct Map {
     h: usize,
 }
 
-fn main() -> Result<()> {
-    let map: Map = parse()?;
+// A beam state is a tile plus the direction the beam is travelling when it
+// enters, encoded as (y * w + x) * 4 + dir
+impl Map {
+    fn states(&self) -> usize {
+        self.w * self.h * 4
+    }
 
-    let cur: V = (0, 0);
-    let dir: V = (1, 0);
+    fn state(&self, x: usize, y: usize, dir: usize) -> usize {
+        (y * self.w + x) * 4 + dir
+    }
 
-    let mut set: S = HashSet::new();
+    fn tile(&self, state: usize) -> usize {
+        state / 4
+    }
 
-    beam(cur, dir, &map, &mut set);
+    fn next(&self, state: usize) -> impl Iterator<Item = usize> + '_ {
+        let dir = state % 4;
+        let (x, y) = ((state / 4) % self.w, (state / 4) / self.w);
+
+        // 4 marks no second beam
+        let dirs = match self.grid[y][x] {
+            EMPTY => [dir, 4],
+            MIRROR_F => [[3, 2, 1, 0][dir], 4],
+            MIRROR_B => [[1, 0, 3, 2][dir], 4],
+            VERT_SPLIT if dir == 0 || dir == 2 => [1, 3],
+            HORIZ_SPLIT if dir == 1 || dir == 3 => [0, 2],
+            _ => [dir, 4], // pass through the pointy end of a splitter
+        };
+
+        dirs.into_iter().filter(|&d| d < 4).filter_map(move |d| {
+            let nx = x as i32 + DIRS[d].0;
+            let ny = y as i32 + DIRS[d].1;
+
+            if nx < 0 || ny < 0 || nx >= self.w as i32 || ny >= self.h as i32 {
+                None
+            } else {
+                Some(self.state(nx as usize, ny as usize, d))
+            }
+        })
+    }
 
-    let locs: HashSet<(i32, i32)> = set.iter().map(|&(l, _)| l).collect();
+    // every beam entering from the edge, for part 2
+    fn edge_starts(&self) -> Vec<usize> {
+        let mut starts = vec![];
 
-    println!("Part 1: {}", locs.len());
+        // left & right
+        for y in 0..self.h {
+            starts.push(self.state(0, y, 0));
+            starts.push(self.state(self.w - 1, y, 2));
+        }
 
-    // part 2
-    let mut starts = vec![];
+        // top & bottom
+        for x in 0..self.w {
+            starts.push(self.state(x, 0, 1));
+            starts.push(self.state(x, self.h - 1, 3));
+        }
 
-    // left & right
-    for y in 0..map.h {
-        starts.push(((0, y as i32), (1, 0)));
-        starts.push(((map.w as i32 - 1, y as i32), (-1, 0)));
+        starts
     }
+}
 
-    // top & bottom
-    for x in 0..map.w {
-        starts.push(((x as i32, 0), (0, 1)));
-        starts.push(((x as i32, map.h as i32 - 1), (0, -1)));
-    }
+#[derive(Debug, Clone)]
+struct BitSet(Vec<u64>);
 
-    let mut max = 0;
-    for &(l, d) in &starts {
-        set.clear();
-        beam(l, d, &map, &mut set);
-        let locs: HashSet<(i32, i32)> = set.iter().map(|&(l, _)| l).collect();
-        if locs.len() > max {
-            max = locs.len();
-        }
+impl BitSet {
+    fn new(n: usize) -> Self {
+        BitSet(vec![0; n.div_ceil(64)])
     }
 
-    println!("Part 2: {}", max);
+    // returns true if newly set
+    fn insert(&mut self, i: usize) -> bool {
+        let (w, b) = (i / 64, 1 << (i % 64));
+        let new = self.0[w] & b == 0;
+        self.0[w] |= b;
 
-    Ok(())
-}
+        new
+    }
 
-fn beam(loc: V, dir: V, map: &Map, set: &mut S) {
-    // catch all out of bounds locs here
-    if loc.0 < 0 || loc.0 > map.w as i32 - 1 || loc.1 < 0 || loc.1 > map.h as i32 - 1 {
-        return;
+    fn union(&mut self, other: &BitSet) {
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            *a |= b;
+        }
     }
 
-    // already been here with same dir
-    if let Some(_) = set.get(&(loc, dir)) {
-        return;
+    fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
     }
+}
 
-    set.insert((loc, dir));
+// usage: p16 [--threads], --threads traces part 2 starts independently across
+// threads instead of sharing results through the component graph
+fn main() -> Result<()> {
+    let threads = std::env::args().any(|a| a == "--threads");
 
-    let cur = map.grid[loc.1 as usize][loc.0 as usize];
+    let map: Map = parse()?;
 
-    let mut next: Vec<(V, V)> = vec![]; // loc, start
+    println!("Part 1: {}", energised(&map, map.state(0, 0, 0)));
 
-    match cur {
-        VERT_SPLIT => {
-            for x in vert_split(loc, dir) {
-                next.push(x);
-            }
-        }
-        HORIZ_SPLIT => {
-            for x in horiz_split(loc, dir) {
-                next.push(x);
+    let starts = map.edge_starts();
+
+    let part2 = if threads {
+        energised_parallel(&map, &starts)
+    } else {
+        energised_scc(&map, &starts).into_iter().max().unwrap_or(0)
+    };
+
+    println!("Part 2: {}", part2);
+
+    Ok(())
+}
+
+// explicit stack so large grids can't overflow the call stack
+fn energised(map: &Map, start: usize) -> usize {
+    let mut seen = BitSet::new(map.states());
+    let mut tiles = BitSet::new(map.w * map.h);
+    let mut stack = vec![start];
+
+    seen.insert(start);
+
+    while let Some(s) = stack.pop() {
+        tiles.insert(map.tile(s));
+
+        for n in map.next(s) {
+            if seen.insert(n) {
+                stack.push(n);
             }
         }
-        MIRROR_F => {
-            next.push(mirror_f(loc, dir));
-        }
-        MIRROR_B => {
-            next.push(mirror_b(loc, dir));
-        }
-        EMPTY => {
-            next.push(((loc.0 + dir.0, loc.1 + dir.1), dir));
-        }
-        _ => unreachable!(),
     }
 
-    // now recurse on each one
-    for n in next {
-        beam(n.0, n.1, map, set);
-    }
+    tiles.len()
 }
 
-// Vert Split |
-fn vert_split(loc: V, dir: V) -> Vec<(V, V)> {
-    let mut v = vec![];
+fn energised_parallel(map: &Map, starts: &[usize]) -> usize {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = starts.len().div_ceil(workers);
+
+    std::thread::scope(|s| {
+        let handles: Vec<_> = starts
+            .chunks(chunk)
+            .map(|c| s.spawn(move || c.iter().map(|&st| energised(map, st)).max().unwrap_or(0)))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .max()
+            .unwrap_or(0)
+    })
+}
 
-    match dir {
-        (1, 0) => {
-            v.push(((loc.0, loc.1 + 1), (0, 1)));
-            v.push(((loc.0, loc.1 - 1), (0, -1)));
-        }
-        (-1, 0) => {
-            v.push(((loc.0, loc.1 + 1), (0, 1)));
-            v.push(((loc.0, loc.1 - 1), (0, -1)));
-        }
-        _ => {
-            v.push(((loc.0 + dir.0, loc.1 + dir.1), dir));
+// Beams loop back on themselves through splitters so the state graph has
+// cycles. Condensing it into strongly connected components gives a DAG where
+// everything a component reaches is its own tiles plus everything its
+// successors reach, so each component's tile set is built once and shared.
+fn energised_scc(map: &Map, starts: &[usize]) -> Vec<usize> {
+    let (comp, order) = tarjan(map, starts);
+    let n = order.len();
+
+    let mut succ: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut preds = vec![0; n];
+
+    for (c, states) in order.iter().enumerate() {
+        for &s in states {
+            for t in map.next(s) {
+                let tc = comp[t];
+                if tc != c && !succ[c].contains(&tc) {
+                    succ[c].push(tc);
+                    preds[tc] += 1;
+                }
+            }
         }
     }
 
-    v
-}
-
-// Horiz Split -
-fn horiz_split(loc: V, dir: V) -> Vec<(V, V)> {
-    let mut v = vec![];
+    // tarjan emits components sinks first so successors are always done.
+    // Sets are dropped once their last predecessor has used them
+    let mut sets: Vec<Option<BitSet>> = vec![None; n];
+    let mut counts = vec![0; n];
 
-    match dir {
-        (0, 1) => {
-            v.push(((loc.0 + 1, loc.1), (1, 0)));
-            v.push(((loc.0 - 1, loc.1), (-1, 0)));
+    for c in 0..n {
+        let mut tiles = BitSet::new(map.w * map.h);
+        for &s in &order[c] {
+            tiles.insert(map.tile(s));
         }
-        (0, -1) => {
-            v.push(((loc.0 + 1, loc.1), (1, 0)));
-            v.push(((loc.0 - 1, loc.1), (-1, 0)));
+
+        for &t in &succ[c] {
+            if let Some(set) = &sets[t] {
+                tiles.union(set);
+            }
+            preds[t] -= 1;
+            if preds[t] == 0 {
+                sets[t] = None;
+            }
         }
-        _ => {
-            v.push(((loc.0 + dir.0, loc.1 + dir.1), dir));
+
+        counts[c] = tiles.len();
+        if preds[c] > 0 {
+            sets[c] = Some(tiles);
         }
     }
 
-    v
+    starts.iter().map(|&s| counts[comp[s]]).collect()
 }
 
-// Mirror /
-fn mirror_f(loc: V, dir: V) -> (V, V) {
-    let new_dir = match dir {
-        (1, 0) => (0, -1), // right
-        (-1, 0) => (0, 1), // left
-        (0, 1) => (-1, 0), // down
-        (0, -1) => (1, 0), // up
-        _ => unreachable!(),
-    };
+// iterative Tarjan over the states reachable from the starts, returns each
+// state's component and the states in each component in reverse topological
+// order
+fn tarjan(map: &Map, starts: &[usize]) -> (Vec<usize>, Vec<Vec<usize>>) {
+    const UNSEEN: usize = usize::MAX;
+
+    let n = map.states();
+    let mut index = vec![UNSEEN; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut comp = vec![UNSEEN; n];
+    let mut stack = vec![];
+    let mut order = vec![];
+    let mut counter = 0;
+
+    for &root in starts {
+        if index[root] != UNSEEN {
+            continue;
+        }
 
-    ((loc.0 + new_dir.0, loc.1 + new_dir.1), new_dir)
-}
+        // (state, successors, next successor to visit)
+        let mut call: Vec<(usize, Vec<usize>, usize)> = vec![];
+
+        index[root] = counter;
+        low[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        call.push((root, map.next(root).collect(), 0));
+
+        while let Some((v, succs, i)) = call.last_mut() {
+            let v = *v;
+
+            if let Some(&w) = succs.get(*i) {
+                *i += 1;
+
+                if index[w] == UNSEEN {
+                    index[w] = counter;
+                    low[w] = counter;
+                    counter += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    call.push((w, map.next(w).collect(), 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
 
-// Mirror \
-fn mirror_b(loc: V, dir: V) -> (V, V) {
-    let new_dir = match dir {
-        (1, 0) => (0, 1),   // right
-        (-1, 0) => (0, -1), // left
-        (0, 1) => (1, 0),   // down
-        (0, -1) => (-1, 0), // up
-        _ => unreachable!(),
-    };
+            call.pop();
+
+            if let Some((parent, _, _)) = call.last() {
+                low[*parent] = low[*parent].min(low[v]);
+            }
+
+            if low[v] == index[v] {
+                let c = order.len();
+                let mut states = vec![];
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    comp[w] = c;
+                    states.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                order.push(states);
+            }
+        }
+    }
 
-    ((loc.0 + new_dir.0, loc.1 + new_dir.1), new_dir)
+    (comp, order)
 }
 
 fn parse() -> Result<Map> {
@@ -1,6 +1,6 @@
-use anyhow::Result;
-use std::collections::BTreeSet;
-use std::collections::HashSet;
+use anyhow::{anyhow, Result};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::io::{self, BufRead};
 
 #[derive(Debug, Clone)]
@@ -10,105 +10,103 @@ struct Map {
     h: usize,
 }
 
-// (hl, x, y, dx, dy, n)
-type Node = BTreeSet<(u32, i32, i32, i32, i32, usize)>;
+// right, down, left, up
+const DIRS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
 
-fn main() -> Result<()> {
-    let map: Map = parse()?;
-
-    println!("Part 1: {}", dijkstra_1(&map));
-
-    println!("Part 2: {}", dijkstra_2(&map));
-
-    Ok(())
-}
+// (min, max) steps in a straight line before turning
+const CRUCIBLE: (usize, usize) = (1, 3);
+const ULTRA_CRUCIBLE: (usize, usize) = (4, 10);
 
-fn dijkstra_1(map: &Map) -> u32 {
-    let mut seen = HashSet::new();
-
-    let mut queue: Node = Node::from([(0, 0, 0, 0, 0, 0)]);
+// usage: p17 [min-max ...], each rule pair given replaces the default parts
+// e.g. p17 1-3 4-10 2-5
+fn main() -> Result<()> {
+    let rules = std::env::args()
+        .skip(1)
+        .map(|a| {
+            let (min, max) = a.split_once('-').ok_or(anyhow!("invalid rule: {}", a))?;
+            Ok((min.parse()?, max.parse()?))
+        })
+        .collect::<Result<Vec<(usize, usize)>>>()?;
 
-    while let Some((heat, x, y, dx, dy, n)) = queue.pop_first() {
-        // check for exit
-        if y == map.h as i32 - 1 && x == map.w as i32 - 1 {
-            return heat;
-        }
+    let map: Map = parse()?;
 
-        // skip if we have visited this tile before
-        if !seen.insert((x, y, dx, dy, n)) {
-            continue;
-        }
+    if rules.is_empty() {
+        let (min, max) = CRUCIBLE;
+        println!(
+            "Part 1: {}",
+            dijkstra(&map, min, max).ok_or(anyhow!("no path"))?
+        );
+
+        let (min, max) = ULTRA_CRUCIBLE;
+        println!(
+            "Part 2: {}",
+            dijkstra(&map, min, max).ok_or(anyhow!("no path"))?
+        );
+    }
 
-        // go straight
-        if n < 3 && (dx, dy) != (0, 0) {
-            let nx = x + dx;
-            let ny = y + dy;
-            if nx >= 0 && nx < map.w as i32 && ny >= 0 && ny < map.h as i32 {
-                let h = heat + map.grid[ny as usize][nx as usize];
-                queue.insert((h, nx, ny, dx, dy, n + 1));
-            }
+    for (min, max) in rules {
+        if min == 0 || max < min {
+            return Err(anyhow!("invalid rule: {}-{}", min, max));
         }
 
-        // try turning
-        for (ndx, ndy) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
-            if (ndx, ndy) != (dx, dy) && (ndx, ndy) != (-dx, -dy) {
-                let nx = x + ndx;
-                let ny = y + ndy;
-
-                if nx >= 0 && nx < map.w as i32 && ny >= 0 && ny < map.h as i32 {
-                    let h = heat + map.grid[ny as usize][nx as usize];
-                    queue.insert((h, nx, ny, ndx, ndy, 1));
-                }
-            }
+        match dijkstra(&map, min, max) {
+            Some(heat) => println!("Rule {}-{}: {}", min, max, heat),
+            None => println!("Rule {}-{}: no path", min, max),
         }
     }
 
-    0
+    Ok(())
 }
 
-fn dijkstra_2(map: &Map) -> u32 {
-    let mut seen = HashSet::new();
+// nodes are (x, y, dir, n) with n the steps taken in a straight line so far,
+// the crucible has to travel at least min_straight before it can turn or stop
+fn dijkstra(map: &Map, min_straight: usize, max_straight: usize) -> Option<u32> {
+    let idx = |x: usize, y: usize, dir: usize, n: usize| {
+        ((y * map.w + x) * 4 + dir) * (max_straight + 1) + n
+    };
 
-    let mut queue: Node = Node::from([(0, 0, 0, 0, 0, 0)]);
+    let mut seen = vec![false; map.w * map.h * 4 * (max_straight + 1)];
 
-    while let Some((heat, x, y, dx, dy, n)) = queue.pop_first() {
+    let mut queue = BinaryHeap::new();
+
+    // no direction at the start, so set off right and down
+    for dir in [0, 1] {
+        queue.push(Reverse((0, 0, 0, dir, 0)));
+    }
+
+    while let Some(Reverse((heat, x, y, dir, n))) = queue.pop() {
         // check for exit
-        if y == map.h as i32 - 1 && x == map.w as i32 - 1 && n >= 4 {
-            return heat;
+        if y == map.h - 1 && x == map.w - 1 && n >= min_straight {
+            return Some(heat);
         }
 
-        // skip if we have visited this tile before
-        if !seen.insert((x, y, dx, dy, n)) {
+        // skip if we have visited this node before
+        if std::mem::replace(&mut seen[idx(x, y, dir, n)], true) {
             continue;
         }
 
-        // go straight
-        if n < 10 && (dx, dy) != (0, 0) {
-            let ny = y + dy;
-            let nx = x + dx;
-            if ny >= 0 && ny < map.h as i32 && nx >= 0 && nx < map.w as i32 {
-                let h = heat + map.grid[ny as usize][nx as usize];
-                queue.insert((h, nx, ny, dx, dy, n + 1));
+        // straight on, or turn left or right once we've gone far enough
+        for ndir in [dir, (dir + 1) % 4, (dir + 3) % 4] {
+            let nn = if ndir == dir { n + 1 } else { 1 };
+
+            if nn > max_straight || (ndir != dir && n < min_straight) {
+                continue;
             }
-        }
 
-        // try turning
-        if n >= 4 || (dx, dy) == (0, 0) {
-            for (ndx, ndy) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
-                if (ndx, ndy) != (dx, dy) && (ndx, ndy) != (-dx, -dy) {
-                    let ny = y + ndy;
-                    let nx = x + ndx;
-
-                    if ny >= 0 && ny < map.h as i32 && nx >= 0 && nx < map.w as i32 {
-                        let h = heat + map.grid[ny as usize][nx as usize];
-                        queue.insert((h, nx, ny, ndx, ndy, 1));
-                    }
+            let nx = x as i32 + DIRS[ndir].0;
+            let ny = y as i32 + DIRS[ndir].1;
+
+            if nx >= 0 && nx < map.w as i32 && ny >= 0 && ny < map.h as i32 {
+                let (nx, ny) = (nx as usize, ny as usize);
+                if !seen[idx(nx, ny, ndir, nn)] {
+                    let h = heat + map.grid[ny][nx];
+                    queue.push(Reverse((h, nx, ny, ndir, nn)));
                 }
             }
         }
     }
 
-    0
+    None
 }
 
 fn parse() -> Result<Map> {
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use aoc_2021::polygon::Polygon;
 use std::io::{self, BufRead};
 
 #[derive(Debug)]
@@ -26,44 +27,21 @@ fn main() -> Result<()> {
 }
 
 fn process(idx: usize, instructions: &[Instruction]) -> i64 {
-    let mut points = vec![];
-    let mut boundary = 0;
-
-    let mut loc = (0i64, 0i64);
-    points.push(loc);
-
-    for i in instructions {
-        boundary += i.len[idx];
-        match i.dir[idx] {
-            Direction::Left => {
-                loc.0 -= i.len[idx];
-            }
-            Direction::Right => {
-                loc.0 += i.len[idx];
-            }
-            Direction::Up => {
-                loc.1 -= i.len[idx];
-            }
-            Direction::Down => {
-                loc.1 += i.len[idx];
-            }
-        }
-        points.push(loc);
-    }
-
-    points.push(points[0]); // dup first point at end for (i + 1) wrap around
+    let steps = instructions.iter().map(|i| {
+        let dir = match i.dir[idx] {
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+        };
 
-    // https://en.wikipedia.org/wiki/Shoelace_formula
-    let area = points
-        .windows(2)
-        .map(|w| (w[0].1 + w[1].1) * (w[0].0 - w[1].0))
-        .sum::<i64>()
-        / 2;
+        (dir, i.len[idx])
+    });
 
-    // https://en.wikipedia.org/wiki/Pick%27s_theorem
-    let internal = area - boundary / 2 + 1;
+    let lagoon = Polygon::from_steps(steps);
 
-    boundary + internal
+    // dug out cubes are the trench itself plus everything inside it
+    lagoon.boundary() + lagoon.interior()
 }
 
 fn parse() -> Result<Vec<Instruction>> {
@@ -1,99 +1,150 @@
-use anyhow::Result;
-use std::collections::{HashMap, HashSet, VecDeque};
+use anyhow::{anyhow, Result};
 use std::io::{self, BufRead};
 
+// p0 is the low corner and p1 the high corner
 #[derive(Debug, Copy, Clone)]
 struct Brick {
     p0: (u32, u32, u32),
     p1: (u32, u32, u32),
 }
 
-fn main() -> Result<()> {
-    let mut bricks = parse()?;
+impl Brick {
+    fn cells(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.p0.1..=self.p1.1).flat_map(move |y| (self.p0.0..=self.p1.0).map(move |x| (x, y)))
+    }
+}
 
-    bricks.sort_unstable_by_key(|b| std::cmp::min(b.p0.2, b.p1.2));
+// The ground and bricks form a DAG with an edge from each brick to the ones
+// resting on it. A brick falls when brick i is removed exactly when every
+// path from the ground to it passes through i, i.e. when i dominates it.
+// Bricks are added in settling order so all supporters come first, and the
+// immediate dominator of a brick is the lowest common ancestor of its
+// supporters in the tree built so far.
+struct Dominators {
+    // binary lifting table, up[k][v] is the 2^k th ancestor of v
+    up: Vec<Vec<usize>>,
+    depth: Vec<usize>,
+}
 
-    for i in 0..bricks.len() {
-        let mut max_z = 1;
-        for j in 0..i {
-            if overlaps_in_z(&bricks[i], &bricks[j]) {
-                max_z = std::cmp::max(max_z, bricks[j].p1.2 + 1);
+impl Dominators {
+    // node 0 is the ground, brick i is node i + 1
+    fn new(supported_by: &[Vec<usize>]) -> Self {
+        let n = supported_by.len() + 1;
+        let levels = (usize::BITS - n.leading_zeros()).max(1) as usize;
+
+        let mut tree = Dominators {
+            up: vec![vec![0; n]; levels],
+            depth: vec![0; n],
+        };
+
+        for (i, below) in supported_by.iter().enumerate() {
+            let idom = below
+                .iter()
+                .map(|&b| b + 1)
+                .reduce(|a, b| tree.lca(a, b))
+                .unwrap_or(0);
+
+            let v = i + 1;
+            tree.depth[v] = tree.depth[idom] + 1;
+            tree.up[0][v] = idom;
+            for k in 1..levels {
+                tree.up[k][v] = tree.up[k - 1][tree.up[k - 1][v]];
             }
         }
 
-        bricks[i].p1.2 -= bricks[i].p0.2 - max_z;
-        bricks[i].p0.2 = max_z;
+        tree
     }
 
-    bricks.sort_unstable_by_key(|b| std::cmp::min(b.p0.2, b.p1.2));
+    fn lca(&self, mut a: usize, mut b: usize) -> usize {
+        if self.depth[a] < self.depth[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
 
-    // bricks are now stable and sorted from min z to max z
+        let diff = self.depth[a] - self.depth[b];
+        for (k, up) in self.up.iter().enumerate() {
+            if diff & (1 << k) != 0 {
+                a = up[a];
+            }
+        }
 
-    let mut supports: HashMap<_, _> = (0..bricks.len()).map(|i| (i, HashSet::new())).collect();
-    let mut supported_by = supports.clone();
+        if a == b {
+            return a;
+        }
 
-    for j in 0..bricks.len() {
-        // lower bricks
-        for i in 0..j {
-            if overlaps_in_z(&bricks[i], &bricks[j]) && bricks[j].p0.2 == bricks[i].p1.2 + 1 {
-                supports.entry(i).or_insert(HashSet::new()).insert(j);
-                supported_by.entry(j).or_insert(HashSet::new()).insert(i);
+        for up in self.up.iter().rev() {
+            if up[a] != up[b] {
+                a = up[a];
+                b = up[b];
             }
         }
-    }
 
-    // part 1
+        self.up[0][a]
+    }
 
-    let mut sum = 0;
+    // number of bricks that fall when each brick is removed. Children always
+    // come after their dominator so a reverse pass accumulates subtree sizes
+    fn falls(&self) -> Vec<usize> {
+        let n = self.depth.len();
+        let mut size = vec![1; n];
 
-    'outer: for i in 0..bricks.len() {
-        // for each brick j that supports brick i
-        for j in &supports[&i] {
-            // if it is the only brick don't count
-            if supported_by[j].len() <= 1 {
-                continue 'outer;
-            }
+        for v in (1..n).rev() {
+            size[self.up[0][v]] += size[v];
         }
 
-        sum += 1;
+        size[1..].iter().map(|s| s - 1).collect()
     }
+}
+
+fn main() -> Result<()> {
+    let mut bricks = parse()?;
 
-    println!("Part 1: {}", sum);
+    bricks.sort_unstable_by_key(|b| b.p0.2);
 
-    // part 2
+    let supported_by = settle(&mut bricks);
 
-    let mut sum = 0;
+    let falls = Dominators::new(&supported_by).falls();
 
-    for i in 0..bricks.len() {
-        let mut q: VecDeque<usize> = supports[&i]
-            .iter()
-            .filter(|j| supported_by[j].len() == 1)
-            .cloned()
-            .collect();
+    // safe to disintegrate if nothing depends on it alone
+    println!("Part 1: {}", falls.iter().filter(|&&f| f == 0).count());
 
-        let mut falling: HashSet<usize> = q.iter().map(|x| *x).collect();
+    println!("Part 2: {}", falls.iter().sum::<usize>());
 
-        while let Some(j) = q.pop_front() {
-            let d: HashSet<usize> = supports[&j].difference(&falling).cloned().collect();
-            for k in d {
-                if supported_by[&k].is_subset(&falling) {
-                    q.push_back(k);
-                    falling.insert(k);
+    Ok(())
+}
+
+// Drop each brick, lowest first, onto a heightmap of the top of the stack in
+// each column, returning the bricks each one rests on.
+fn settle(bricks: &mut [Brick]) -> Vec<Vec<usize>> {
+    let w = bricks.iter().map(|b| b.p1.0).max().unwrap_or(0) as usize + 1;
+    let h = bricks.iter().map(|b| b.p1.1).max().unwrap_or(0) as usize + 1;
+
+    // (height of the top, brick it belongs to)
+    let mut top: Vec<(u32, Option<usize>)> = vec![(0, None); w * h];
+    let mut supported_by = vec![vec![]; bricks.len()];
+
+    for (i, brick) in bricks.iter_mut().enumerate() {
+        let idx = |(x, y): (u32, u32)| y as usize * w + x as usize;
+
+        let rest = brick.cells().map(|c| top[idx(c)].0).max().unwrap_or(0);
+
+        let below = &mut supported_by[i];
+        for c in brick.cells() {
+            if let (z, Some(j)) = top[idx(c)] {
+                if z == rest && !below.contains(&j) {
+                    below.push(j);
                 }
             }
         }
 
-        sum += falling.len();
-    }
+        brick.p1.2 -= brick.p0.2 - (rest + 1);
+        brick.p0.2 = rest + 1;
 
-    println!("Part 2: {}", sum);
-
-    Ok(())
-}
+        for c in brick.cells() {
+            top[idx(c)] = (brick.p1.2, Some(i));
+        }
+    }
 
-fn overlaps_in_z(b0: &Brick, b1: &Brick) -> bool {
-    use std::cmp::{max, min};
-    max(b0.p0.0, b1.p0.0) <= min(b0.p1.0, b1.p1.0) && max(b0.p0.1, b1.p0.1) <= min(b0.p1.1, b1.p1.1)
+    supported_by
 }
 
 fn parse() -> Result<Vec<Brick>> {
@@ -102,14 +153,18 @@ fn parse() -> Result<Vec<Brick>> {
     for line in io::stdin().lock().lines() {
         let line = line?;
 
-        let n: Vec<_> = line
+        let n: Vec<u32> = line
             .split(&['~', ','])
             .map(|s| s.parse())
             .collect::<Result<_, _>>()?;
 
+        if n.len() != 6 {
+            return Err(anyhow!("invalid brick: {}", line));
+        }
+
         bricks.push(Brick {
-            p0: (n[0], n[1], n[2]),
-            p1: (n[3], n[4], n[5]),
+            p0: (n[0].min(n[3]), n[1].min(n[4]), n[2].min(n[5])),
+            p1: (n[0].max(n[3]), n[1].max(n[4]), n[2].max(n[5])),
         });
     }
 
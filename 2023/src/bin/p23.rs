@@ -1,8 +1,9 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 const PATH: char = '.';
 const FORREST: char = '#';
@@ -22,45 +23,64 @@ struct Map {
     end: (usize, usize),
 }
 
-type Graph = HashMap<((usize, usize), (usize, usize)), usize>;
+// junctions compressed to small integer ids, start is always 0 and end 1
+#[derive(Debug, Clone)]
+struct Graph {
+    // (neighbour, steps)
+    adj: Vec<Vec<(usize, usize)>>,
+    start: usize,
+    end: usize,
+}
+
+// depth of the path prefixes handed out to worker threads
+const SPLIT_DEPTH: usize = 6;
 
+// usage: p23 [--threads]
 fn main() -> Result<()> {
+    let threads = std::env::args().any(|a| a == "--threads");
+
     let map: Map = parse()?;
 
     let vertices = find_vertices(&map);
 
+    if vertices.len() > 64 {
+        return Err(anyhow!(
+            "too many junctions for a u64 bitmask: {}",
+            vertices.len()
+        ));
+    }
+
     let graph = build_graph(&map, &vertices, true);
 
-    println!(
-        "Part 1: {}",
-        dfs(map.start, &graph, &map, &mut HashSet::new())
-    );
+    println!("Part 1: {}", longest(&graph, threads));
 
     let graph = build_graph(&map, &vertices, false);
 
-    println!(
-        "Part 2: {}",
-        dfs(map.start, &graph, &map, &mut HashSet::new())
-    );
+    println!("Part 2: {}", longest(&graph, threads));
 
     Ok(())
 }
 
 fn build_graph(map: &Map, vertices: &[(usize, usize)], slopes: bool) -> Graph {
-    let mut graph: Graph = HashMap::new();
+    let ids: HashMap<(usize, usize), usize> =
+        vertices.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+
+    let mut adj = vec![vec![]; vertices.len()];
 
-    for (sx, sy) in vertices {
+    for (id, (sx, sy)) in vertices.iter().enumerate() {
         let mut stack = VecDeque::from([(0, *sx, *sy)]);
 
         let mut visited = HashSet::from([(*sx, *sy)]);
 
         while let Some((n, x, y)) = stack.pop_front() {
-            if n != 0 && vertices.contains(&(x, y)) {
-                graph.insert(((*sx, *sy), (x, y)), n);
-                continue;
+            if n != 0 {
+                if let Some(&other) = ids.get(&(x, y)) {
+                    adj[id].push((other, n));
+                    continue;
+                }
             }
 
-            for (nx, ny) in neighbours((x, y), &map, slopes) {
+            for (nx, ny) in neighbours((x, y), map, slopes) {
                 if visited.contains(&(nx, ny)) {
                     continue;
                 }
@@ -71,7 +91,11 @@ fn build_graph(map: &Map, vertices: &[(usize, usize)], slopes: bool) -> Graph {
         }
     }
 
-    graph
+    Graph {
+        adj,
+        start: 0,
+        end: 1,
+    }
 }
 
 fn find_vertices(map: &Map) -> Vec<(usize, usize)> {
@@ -81,7 +105,7 @@ fn find_vertices(map: &Map) -> Vec<(usize, usize)> {
         for x in 0..map.w {
             // always disabling slopes seems to work here.
             // maybe due to vertices always being non slopes?
-            if map.grid[y][x] != FORREST && neighbours((x, y), &map, false).len() >= 3 {
+            if map.grid[y][x] != FORREST && neighbours((x, y), map, false).len() >= 3 {
                 vertices.push((x, y));
             }
         }
@@ -90,24 +114,130 @@ fn find_vertices(map: &Map) -> Vec<(usize, usize)> {
     vertices
 }
 
-fn dfs(pt: (usize, usize), graph: &Graph, map: &Map, visited: &mut HashSet<(usize, usize)>) -> i32 {
-    let mut d = i32::MIN;
+struct Search<'a> {
+    graph: &'a Graph,
+    // the junction leading to the end, once reached we must head to the end
+    // otherwise that route is blocked for good
+    exit: Option<(usize, usize)>,
+    // longest edge into each vertex, each vertex added to the path adds at
+    // most this much so summing unvisited vertices gives an upper bound
+    max_in: Vec<usize>,
+    best: AtomicUsize,
+}
 
-    if pt == map.end {
-        return 0;
+impl<'a> Search<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        let mut max_in = vec![0; graph.adj.len()];
+        for edges in &graph.adj {
+            for &(n, d) in edges {
+                max_in[n] = max_in[n].max(d);
+            }
+        }
+
+        let into_end: Vec<_> = (0..graph.adj.len())
+            .filter_map(|v| {
+                graph.adj[v]
+                    .iter()
+                    .find(|&&(n, _)| n == graph.end)
+                    .map(|&(_, d)| (v, d))
+            })
+            .collect();
+
+        let exit = if into_end.len() == 1 {
+            Some(into_end[0])
+        } else {
+            None
+        };
+
+        Search {
+            graph,
+            exit,
+            max_in,
+            best: AtomicUsize::new(0),
+        }
     }
 
-    visited.insert(pt);
+    fn dfs(&self, node: usize, visited: u64, dist: usize, bound: usize) {
+        if node == self.graph.end {
+            self.best.fetch_max(dist, Ordering::Relaxed);
+            return;
+        }
+
+        if let Some((exit, d)) = self.exit {
+            if node == exit {
+                self.best.fetch_max(dist + d, Ordering::Relaxed);
+                return;
+            }
+        }
 
-    for n in graph.keys().filter(|p| pt.0 == p.0 .0 && pt.1 == p.0 .1) {
-        if !visited.contains(&n.1) {
-            d = std::cmp::max(d, dfs(n.1, graph, map, visited) + graph[&(pt, n.1)] as i32);
+        if dist + bound <= self.best.load(Ordering::Relaxed) {
+            return;
+        }
+
+        for &(n, d) in &self.graph.adj[node] {
+            if visited & (1 << n) == 0 {
+                self.dfs(n, visited | (1 << n), dist + d, bound - self.max_in[n]);
+            }
+        }
+    }
+
+    // collect partial paths SPLIT_DEPTH edges deep as independent work items
+    fn prefixes(
+        &self,
+        node: usize,
+        visited: u64,
+        dist: usize,
+        bound: usize,
+        depth: usize,
+        out: &mut Vec<(usize, u64, usize, usize)>,
+    ) {
+        if depth == 0 || node == self.graph.end || Some(node) == self.exit.map(|e| e.0) {
+            out.push((node, visited, dist, bound));
+            return;
+        }
+
+        for &(n, d) in &self.graph.adj[node] {
+            if visited & (1 << n) == 0 {
+                let bound = bound - self.max_in[n];
+                self.prefixes(n, visited | (1 << n), dist + d, bound, depth - 1, out);
+            }
         }
     }
+}
+
+fn longest(graph: &Graph, threads: bool) -> usize {
+    let search = Search::new(graph);
+
+    let visited = 1 << graph.start;
+    let bound: usize = (0..graph.adj.len())
+        .filter(|&v| v != graph.start)
+        .map(|v| search.max_in[v])
+        .sum();
+
+    if !threads {
+        search.dfs(graph.start, visited, 0, bound);
+        return search.best.into_inner();
+    }
 
-    visited.remove(&pt);
+    let mut work = vec![];
+    search.prefixes(graph.start, visited, 0, bound, SPLIT_DEPTH, &mut work);
+
+    let next = AtomicUsize::new(0);
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    std::thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| {
+                while let Some(&(node, visited, dist, bound)) =
+                    work.get(next.fetch_add(1, Ordering::Relaxed))
+                {
+                    search.dfs(node, visited, dist, bound);
+                }
+            });
+        }
+    });
 
-    d
+    search.best.into_inner()
 }
 
 fn neighbours(pt: (usize, usize), map: &Map, slopes: bool) -> HashSet<(usize, usize)> {
@@ -0,0 +1,69 @@
+// Simple lattice polygons, shared by days 10 and 18
+
+pub type Point = (i64, i64);
+
+#[derive(Debug, Clone, Default)]
+pub struct Polygon {
+    pub vertices: Vec<Point>,
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Polygon {
+    // cells of a traced loop, in order
+    pub fn from_loop(cells: &[(usize, usize)]) -> Self {
+        Polygon {
+            vertices: cells.iter().map(|&(x, y)| (x as i64, y as i64)).collect(),
+        }
+    }
+
+    // walk from the origin taking (direction, length) steps, direction is a
+    // unit vector e.g. (1, 0) for right
+    pub fn from_steps(steps: impl IntoIterator<Item = (Point, i64)>) -> Self {
+        let mut loc = (0, 0);
+        let mut vertices = vec![loc];
+
+        for ((dx, dy), len) in steps {
+            loc = (loc.0 + dx * len, loc.1 + dy * len);
+            vertices.push(loc);
+        }
+
+        // closing the loop returns to the origin, don't count it twice
+        if vertices.len() > 1 && vertices.last() == vertices.first() {
+            vertices.pop();
+        }
+
+        Polygon { vertices }
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let n = self.vertices.len();
+
+        (0..n).map(move |i| (self.vertices[i], self.vertices[(i + 1) % n]))
+    }
+
+    // https://en.wikipedia.org/wiki/Shoelace_formula
+    pub fn double_area(&self) -> i64 {
+        self.edges()
+            .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+            .sum::<i64>()
+            .abs()
+    }
+
+    // lattice points on the edges
+    pub fn boundary(&self) -> i64 {
+        self.edges().map(|(a, b)| gcd(b.0 - a.0, b.1 - a.1)).sum()
+    }
+
+    // lattice points strictly inside
+    // https://en.wikipedia.org/wiki/Pick%27s_theorem
+    pub fn interior(&self) -> i64 {
+        (self.double_area() - self.boundary() + 2) / 2
+    }
+}